@@ -0,0 +1,297 @@
+use crate::common::collections::dense_grid::{ArrayGrid, DenseGrid};
+use crate::common::collections::interval::Interval;
+use crate::common::collections::sparse_grid::{HashGrid, SparseGrid};
+use std::mem;
+
+/// Grids an [`Automaton`] can run on.
+pub trait CellGrid<V>: Clone {
+    /// Value at the position. Sparse grids return the default value where nothing is stored.
+    fn read(&self, x: usize, y: usize) -> V;
+    fn write(&mut self, x: usize, y: usize, value: V);
+    /// Resets every cell to the default value, keeping the allocation around.
+    fn clear(&mut self);
+}
+
+impl<V: Default + Clone + PartialEq> CellGrid<V> for ArrayGrid<V> {
+    fn read(&self, x: usize, y: usize) -> V {
+        self.get_pos(x, y).clone()
+    }
+
+    fn write(&mut self, x: usize, y: usize, value: V) {
+        self.set_pos(x, y, value);
+    }
+
+    fn clear(&mut self) {
+        self.underlying.fill(V::default());
+    }
+}
+
+/// Only non-default values are stored, so the grid stays as sparse as the state it holds.
+impl<V: Default + Clone + PartialEq> CellGrid<V> for HashGrid<V> {
+    fn read(&self, x: usize, y: usize) -> V {
+        self.get_pos(x, y).cloned().unwrap_or_default()
+    }
+
+    fn write(&mut self, x: usize, y: usize, value: V) {
+        if value == V::default() {
+            self.remove(&(x, y));
+        } else {
+            self.set_pos(x, y, value);
+        }
+    }
+
+    fn clear(&mut self) {
+        (**self).clear();
+    }
+}
+
+/// What neighbours beyond the edge of the tracked area look like.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Boundary<V> {
+    /// Cells outside always hold this value and are never updated.
+    Fixed(V),
+    /// Opposite edges are adjacent.
+    Wrap,
+    /// Unbounded plane where every cell outside holds the same background state, which evolves
+    /// with the rule like any other cell. The tracked area grows each step by the reach of the
+    /// neighbourhood, so the grid needs room for that around its cells.
+    Infinite(V),
+}
+
+/// Synchronous cellular automaton: every step computes each cell from its own value and the values
+/// of its neighbours, in the order given by `neighbourhood`, all read from the previous state.
+#[derive(Clone)]
+pub struct Automaton<G, V> {
+    pub grid: G,
+    pub x: Interval<usize>,
+    pub y: Interval<usize>,
+    pub steps: usize,
+    boundary: Boundary<V>,
+    neighbourhood: Vec<(i32, i32)>,
+    back: G,
+}
+
+impl<G: CellGrid<V>, V: Clone + PartialEq> Automaton<G, V> {
+    /// Automaton over the cells of `grid` within `x` and `y`.
+    pub fn new(
+        grid: G,
+        x: Interval<usize>,
+        y: Interval<usize>,
+        neighbourhood: &[(i32, i32)],
+        boundary: Boundary<V>,
+    ) -> Self {
+        let back = grid.clone();
+        Automaton {
+            grid,
+            x,
+            y,
+            steps: 0,
+            boundary,
+            neighbourhood: neighbourhood.to_vec(),
+            back,
+        }
+    }
+
+    /// Current background state for infinite boundaries.
+    pub fn background(&self) -> Option<&V> {
+        match &self.boundary {
+            Boundary::Infinite(background) => Some(background),
+            _ => None,
+        }
+    }
+
+    fn read(&self, x: i64, y: i64) -> V {
+        let inside =
+            |v: i64, range: &Interval<usize>| v >= range.start as i64 && v <= range.end as i64;
+        if inside(x, &self.x) && inside(y, &self.y) {
+            return self.grid.read(x as usize, y as usize);
+        }
+
+        match &self.boundary {
+            Boundary::Fixed(value) | Boundary::Infinite(value) => value.clone(),
+            Boundary::Wrap => {
+                let wrap = |v: i64, range: &Interval<usize>| {
+                    (v - range.start as i64).rem_euclid(range.len() as i64) as usize + range.start
+                };
+                self.grid.read(wrap(x, &self.x), wrap(y, &self.y))
+            }
+        }
+    }
+
+    /// Values of the neighbours of a cell, in neighbourhood order.
+    pub fn neighbour_values(&self, x: usize, y: usize) -> Vec<V> {
+        self.neighbourhood
+            .iter()
+            .map(|&(dx, dy)| self.read(x as i64 + dx as i64, y as i64 + dy as i64))
+            .collect()
+    }
+
+    /// Advances by one step and returns the number of cells that changed.
+    pub fn step<RuleF>(&mut self, rule: RuleF) -> usize
+    where
+        RuleF: Fn(&V, &[V]) -> V,
+    {
+        let (x, y) = match self.boundary {
+            Boundary::Infinite(_) => {
+                let reach = self
+                    .neighbourhood
+                    .iter()
+                    .map(|&(dx, dy)| dx.unsigned_abs().max(dy.unsigned_abs()) as usize)
+                    .max()
+                    .unwrap_or(0);
+                (self.x.grow(reach), self.y.grow(reach))
+            }
+            _ => (self.x, self.y),
+        };
+
+        self.back.clear();
+        let mut num_changed = 0;
+        for cur_y in y.start..=y.end {
+            for cur_x in x.start..=x.end {
+                let cur = self.read(cur_x as i64, cur_y as i64);
+                let next = rule(&cur, &self.neighbour_values(cur_x, cur_y));
+                if next != cur {
+                    num_changed += 1;
+                }
+                self.back.write(cur_x, cur_y, next);
+            }
+        }
+
+        if let Boundary::Infinite(background) = &mut self.boundary {
+            let neighbours = vec![background.clone(); self.neighbourhood.len()];
+            *background = rule(background, &neighbours);
+        }
+
+        mem::swap(&mut self.grid, &mut self.back);
+        self.x = x;
+        self.y = y;
+        self.steps += 1;
+        num_changed
+    }
+
+    /// Runs a fixed number of steps.
+    pub fn run<RuleF>(&mut self, rule: RuleF, num_steps: usize)
+    where
+        RuleF: Fn(&V, &[V]) -> V,
+    {
+        for _ in 0..num_steps {
+            self.step(&rule);
+        }
+    }
+
+    /// Steps until `stop` accepts the automaton after a step, given the number of cells changed in
+    /// it. Returns the total step count at that point, or None if `max_steps` ran out first.
+    pub fn run_until<RuleF, StopF>(
+        &mut self,
+        rule: RuleF,
+        stop: StopF,
+        max_steps: usize,
+    ) -> Option<usize>
+    where
+        RuleF: Fn(&V, &[V]) -> V,
+        StopF: Fn(&Self, usize) -> bool,
+    {
+        for _ in 0..max_steps {
+            let num_changed = self.step(&rule);
+            if stop(self, num_changed) {
+                return Some(self.steps);
+            }
+        }
+        None
+    }
+
+    /// Steps until a step changes nothing.
+    pub fn run_until_stable<RuleF>(&mut self, rule: RuleF, max_steps: usize) -> Option<usize>
+    where
+        RuleF: Fn(&V, &[V]) -> V,
+    {
+        self.run_until(rule, |_, num_changed| num_changed == 0, max_steps)
+    }
+}
+
+impl<V: Default + Clone + PartialEq> Automaton<ArrayGrid<V>, V> {
+    /// Automaton over a whole dense grid. Infinite boundaries need a sparse grid to grow into.
+    pub fn dense(grid: ArrayGrid<V>, neighbourhood: &[(i32, i32)], boundary: Boundary<V>) -> Self {
+        assert!(
+            !matches!(boundary, Boundary::Infinite(_)),
+            "Dense grids cannot grow for an infinite boundary"
+        );
+        let x = Interval::new(0, grid.x_size - 1);
+        let y = Interval::new(0, grid.y_size - 1);
+        Automaton::new(grid, x, y, neighbourhood, boundary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::collections::NEIGHBOURS_2D;
+
+    fn life(cur: &bool, neighbours: &[bool]) -> bool {
+        let alive = neighbours.iter().filter(|&&n| n).count();
+        alive == 3 || (*cur && alive == 2)
+    }
+
+    fn to_bools(s: &str) -> ArrayGrid<bool> {
+        let grid: ArrayGrid<char> = s.parse().unwrap();
+        ArrayGrid {
+            x_size: grid.x_size,
+            y_size: grid.y_size,
+            underlying: grid.underlying.iter().map(|&ch| ch == '#').collect(),
+        }
+    }
+
+    #[test]
+    fn test_fixed_and_wrap() {
+        let blinker = to_bools(".....\n..#..\n..#..\n..#..\n.....");
+        let mut automaton =
+            Automaton::dense(blinker.clone(), &NEIGHBOURS_2D, Boundary::Fixed(false));
+        assert_eq!(automaton.step(life), 4);
+        assert_eq!(
+            automaton.grid,
+            to_bools(".....\n.....\n.###.\n.....\n.....")
+        );
+        assert_eq!(automaton.run_until_stable(life, 10), None);
+        assert_eq!(automaton.steps, 11);
+
+        // A glider on a torus is back where it started after four steps per cell it moved
+        let glider = to_bools(".#....\n..#...\n###...\n......\n......\n......");
+        let mut automaton = Automaton::dense(glider.clone(), &NEIGHBOURS_2D, Boundary::Wrap);
+        automaton.run(life, 24);
+        assert_eq!(automaton.grid, glider);
+
+        // Without wrapping it dies in the corner as a block
+        let mut automaton = Automaton::dense(glider, &NEIGHBOURS_2D, Boundary::Fixed(false));
+        let steps = automaton.run_until_stable(life, 100).unwrap();
+        assert!(steps < 100);
+        assert_eq!(automaton.grid.underlying.iter().filter(|&&c| c).count(), 4);
+    }
+
+    #[test]
+    fn test_infinite() {
+        // Everything lit turns off and vice versa, including the background
+        let invert = |cur: &bool, _: &[bool]| !cur;
+        let mut grid = HashGrid::new();
+        grid.write(10, 10, true);
+        let mut automaton = Automaton::new(
+            grid,
+            Interval::new(10, 10),
+            Interval::new(10, 10),
+            &NEIGHBOURS_2D,
+            Boundary::Infinite(false),
+        );
+
+        assert_eq!(automaton.step(invert), 9);
+        assert_eq!(automaton.x, Interval::new(9, 11));
+        assert_eq!(automaton.background(), Some(&true));
+        assert_eq!(automaton.grid.len(), 8);
+        assert!(!automaton.grid.read(10, 10));
+
+        assert_eq!(
+            automaton.run_until(invert, |a, _| a.background() == Some(&false), 5),
+            Some(2)
+        );
+        assert_eq!(automaton.grid.len(), 1);
+        assert_eq!(automaton.neighbour_values(0, 0), vec![false; 8]);
+    }
+}
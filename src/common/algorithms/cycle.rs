@@ -0,0 +1,267 @@
+use crate::common::error::CommonError;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Eventually periodic sequence `x0, f(x0), f(f(x0)), ...`: the state at step `start` is the first
+/// one that comes back, and it does so every `period` steps.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Cycle {
+    pub start: usize,
+    pub period: usize,
+}
+
+impl Cycle {
+    /// True if the sequence settles on a single state.
+    pub fn is_fixed_point(&self) -> bool {
+        self.period == 1
+    }
+
+    /// Step within the first pass through the cycle that holds the same state as step `n`.
+    pub fn reduce(&self, n: usize) -> usize {
+        if n < self.start {
+            n
+        } else {
+            self.start + (n - self.start) % self.period
+        }
+    }
+}
+
+// Step function that gives up after a fixed number of calls
+struct Budgeted<F> {
+    step: F,
+    budget: usize,
+    used: usize,
+}
+
+impl<F> Budgeted<F> {
+    fn new(step: F, budget: usize) -> Self {
+        Budgeted {
+            step,
+            budget,
+            used: 0,
+        }
+    }
+
+    fn call<S>(&mut self, state: &S) -> Result<S, CommonError>
+    where
+        F: Fn(&S) -> S,
+    {
+        if self.used == self.budget {
+            return Err(CommonError::StepBudget(self.budget));
+        }
+        self.used += 1;
+        Ok((self.step)(state))
+    }
+}
+
+/// Brent's algorithm, which only keeps two states around. `budget` caps the number of calls to
+/// `step`, a StepBudget error is returned when it runs out.
+pub fn brent<S, F>(initial: &S, step: F, budget: usize) -> Result<Cycle, CommonError>
+where
+    S: Clone + PartialEq,
+    F: Fn(&S) -> S,
+{
+    let mut step = Budgeted::new(step, budget);
+
+    // Find the period by letting the hare run ahead in powers of two
+    let mut power = 1;
+    let mut period = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step.call(initial)?;
+    while tortoise != hare {
+        if power == period {
+            tortoise = hare.clone();
+            power *= 2;
+            period = 0;
+        }
+        hare = step.call(&hare)?;
+        period += 1;
+    }
+
+    // With the hare a period ahead, both meet at the start of the cycle
+    let mut tortoise = initial.clone();
+    let mut hare = initial.clone();
+    for _ in 0..period {
+        hare = step.call(&hare)?;
+    }
+    let mut start = 0;
+    while tortoise != hare {
+        tortoise = step.call(&tortoise)?;
+        hare = step.call(&hare)?;
+        start += 1;
+    }
+
+    Ok(Cycle { start, period })
+}
+
+/// Floyd's tortoise and hare, with the same budget semantics as [`brent`].
+pub fn floyd<S, F>(initial: &S, step: F, budget: usize) -> Result<Cycle, CommonError>
+where
+    S: Clone + PartialEq,
+    F: Fn(&S) -> S,
+{
+    let mut step = Budgeted::new(step, budget);
+
+    let mut tortoise = step.call(initial)?;
+    let mut hare = step.call(&tortoise)?;
+    while tortoise != hare {
+        tortoise = step.call(&tortoise)?;
+        let next = step.call(&hare)?;
+        hare = step.call(&next)?;
+    }
+
+    let mut start = 0;
+    let mut tortoise = initial.clone();
+    while tortoise != hare {
+        tortoise = step.call(&tortoise)?;
+        hare = step.call(&hare)?;
+        start += 1;
+    }
+
+    let mut period = 1;
+    let mut hare = step.call(&tortoise)?;
+    while tortoise != hare {
+        hare = step.call(&hare)?;
+        period += 1;
+    }
+
+    Ok(Cycle { start, period })
+}
+
+// Steps until a state repeats or `last_step` is reached, whichever comes first
+fn record_history<S, F>(
+    initial: &S,
+    step: &mut Budgeted<F>,
+    last_step: usize,
+) -> Result<(Vec<S>, Option<Cycle>), CommonError>
+where
+    S: Clone + Eq + Hash,
+    F: Fn(&S) -> S,
+{
+    let mut seen: HashMap<S, usize> = HashMap::new();
+    let mut history = vec![initial.clone()];
+    seen.insert(initial.clone(), 0);
+
+    while history.len() <= last_step {
+        let next = step.call(history.last().unwrap())?;
+        if let Some(&start) = seen.get(&next) {
+            let period = history.len() - start;
+            return Ok((history, Some(Cycle { start, period })));
+        }
+        seen.insert(next.clone(), history.len());
+        history.push(next);
+    }
+
+    Ok((history, None))
+}
+
+/// Every state up to the first repeat, and the cycle it closes. Costs memory for the whole history
+/// but calls `step` only once per state.
+pub fn find_cycle_hashed<S, F>(
+    initial: &S,
+    step: F,
+    budget: usize,
+) -> Result<(Vec<S>, Cycle), CommonError>
+where
+    S: Clone + Eq + Hash,
+    F: Fn(&S) -> S,
+{
+    let mut step = Budgeted::new(step, budget);
+    // The budget runs out long before usize::MAX steps
+    let (history, cycle) = record_history(initial, &mut step, usize::MAX)?;
+    Ok((history, cycle.unwrap()))
+}
+
+/// State after `n` steps, skipping whole cycles once the sequence repeats, so `n` can be far larger
+/// than anything that could be simulated as long as the sequence repeats within `budget` steps.
+pub fn state_at<S, F>(initial: &S, step: F, n: usize, budget: usize) -> Result<S, CommonError>
+where
+    S: Clone + Eq + Hash,
+    F: Fn(&S) -> S,
+{
+    let mut step = Budgeted::new(step, budget);
+    let (mut history, cycle) = record_history(initial, &mut step, n)?;
+
+    Ok(match cycle {
+        Some(cycle) => history.swap_remove(cycle.reduce(n)),
+        None => history.pop().unwrap(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // x -> x^2 + 1 mod 255 from 3 runs 3, 10, 101, 2, 5, 26, 167, 95, 101, ... so the cycle
+    // starts at step 2 with period 6
+    fn pseudo_random(x: &u64) -> u64 {
+        (x * x + 1) % 255
+    }
+
+    #[test]
+    fn test_detectors_agree() {
+        let expected = Cycle {
+            start: 2,
+            period: 6,
+        };
+        assert_eq!(brent(&3, pseudo_random, 100).unwrap(), expected);
+        assert_eq!(floyd(&3, pseudo_random, 100).unwrap(), expected);
+
+        let (history, cycle) = find_cycle_hashed(&3, pseudo_random, 100).unwrap();
+        assert_eq!(cycle, expected);
+        assert_eq!(history, vec![3, 10, 101, 2, 5, 26, 167, 95]);
+
+        for initial in 0..255u64 {
+            let hashed = find_cycle_hashed(&initial, pseudo_random, 1000).unwrap().1;
+            assert_eq!(brent(&initial, pseudo_random, 1000).unwrap(), hashed);
+            assert_eq!(floyd(&initial, pseudo_random, 1000).unwrap(), hashed);
+        }
+    }
+
+    #[test]
+    fn test_fixed_point() {
+        // Halving settles on zero
+        let cycle = brent(&1000u64, |x| x / 2, 100).unwrap();
+        assert!(cycle.is_fixed_point());
+        assert_eq!(cycle.start, 10);
+    }
+
+    #[test]
+    fn test_state_at() {
+        let huge = 1_000_000_000_000_000_000;
+        let cycle = Cycle {
+            start: 2,
+            period: 6,
+        };
+        let mut expected = 3;
+        for _ in 0..cycle.reduce(huge) {
+            expected = pseudo_random(&expected);
+        }
+        assert_eq!(state_at(&3, pseudo_random, huge, 100).unwrap(), expected);
+        assert_eq!(state_at(&3, pseudo_random, 0, 100).unwrap(), 3);
+        assert_eq!(state_at(&3, pseudo_random, 3, 100).unwrap(), 2);
+        // Reaching n before the budget is fine even without a cycle
+        assert_eq!(state_at(&0u64, |x| x + 1, 50, 100).unwrap(), 50);
+    }
+
+    #[test]
+    fn test_budget() {
+        let counter = |x: &u64| x + 1;
+        assert!(matches!(
+            brent(&0, counter, 1000),
+            Err(CommonError::StepBudget(1000))
+        ));
+        assert!(matches!(
+            floyd(&0, counter, 1000),
+            Err(CommonError::StepBudget(1000))
+        ));
+        assert!(matches!(
+            find_cycle_hashed(&0, counter, 1000),
+            Err(CommonError::StepBudget(1000))
+        ));
+        assert!(matches!(
+            state_at(&0, counter, usize::MAX, 1000),
+            Err(CommonError::StepBudget(1000))
+        ));
+    }
+}
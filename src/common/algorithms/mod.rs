@@ -1,2 +1,4 @@
+pub mod automaton;
+pub mod cycle;
 pub mod grid;
 pub mod search;
@@ -1,5 +1,7 @@
-use std::collections::{BTreeSet, VecDeque};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, VecDeque};
 use std::fmt::Debug;
+use std::ops::Add;
 
 pub fn dfs<'a, State, CheckF, ExpandF>(
     state: State,
@@ -32,3 +34,161 @@ where
 
     None
 }
+
+/// Breadth-first traversal from `start`. Returns every reachable node together with its parent
+/// in the resulting spanning tree (`None` for `start`), in visiting order, so parents always
+/// come before their children.
+pub fn bfs<Node, ExpandF>(start: Node, expand: ExpandF) -> Vec<(Node, Option<Node>)>
+where
+    Node: Clone + Ord,
+    ExpandF: Fn(&Node) -> Vec<Node>,
+{
+    let mut queue: VecDeque<Node> = VecDeque::new();
+    let mut visited: BTreeSet<Node> = BTreeSet::new();
+    let mut result = vec![(start.clone(), None)];
+
+    visited.insert(start.clone());
+    queue.push_back(start);
+
+    while let Some(cur) = queue.pop_front() {
+        for next in expand(&cur) {
+            if visited.insert(next.clone()) {
+                result.push((next.clone(), Some(cur.clone())));
+                queue.push_back(next);
+            }
+        }
+    }
+
+    result
+}
+
+/// A* search from `start` to the first state accepted by `is_goal`. `expand` yields successors with
+/// the cost of the move leading to them, and `heuristic` has to be admissible, i.e. never overestimate
+/// the remaining cost, for the result to be optimal. Returns the total cost and the path, both
+/// endpoints included.
+pub fn astar<State, Cost, GoalF, ExpandF, HeuristicF>(
+    start: State,
+    is_goal: GoalF,
+    expand: ExpandF,
+    heuristic: HeuristicF,
+) -> Option<(Cost, Vec<State>)>
+where
+    State: Clone + Ord,
+    Cost: Copy + Ord + Default + Add<Output = Cost>,
+    GoalF: Fn(&State) -> bool,
+    ExpandF: Fn(&State) -> Vec<(State, Cost)>,
+    HeuristicF: Fn(&State) -> Cost,
+{
+    let mut queue = BinaryHeap::new();
+    let mut best: BTreeMap<State, Cost> = BTreeMap::new();
+    let mut parents: BTreeMap<State, State> = BTreeMap::new();
+
+    best.insert(start.clone(), Cost::default());
+    queue.push(Reverse((heuristic(&start), Cost::default(), start)));
+
+    while let Some(Reverse((_, cost, cur))) = queue.pop() {
+        // Stale entry, the state was reached more cheaply after this one was queued
+        if best.get(&cur).is_some_and(|&b| b < cost) {
+            continue;
+        }
+
+        if is_goal(&cur) {
+            let mut path = vec![cur];
+            while let Some(parent) = parents.get(path.last().unwrap()) {
+                path.push(parent.clone());
+            }
+            path.reverse();
+            return Some((cost, path));
+        }
+
+        for (next, step_cost) in expand(&cur) {
+            let next_cost = cost + step_cost;
+            if best.get(&next).is_none_or(|&b| next_cost < b) {
+                best.insert(next.clone(), next_cost);
+                parents.insert(next.clone(), cur.clone());
+                queue.push(Reverse((next_cost + heuristic(&next), next_cost, next)));
+            }
+        }
+    }
+
+    None
+}
+
+/// Dijkstra's algorithm, which is A* without a heuristic.
+pub fn dijkstra<State, Cost, GoalF, ExpandF>(
+    start: State,
+    is_goal: GoalF,
+    expand: ExpandF,
+) -> Option<(Cost, Vec<State>)>
+where
+    State: Clone + Ord,
+    Cost: Copy + Ord + Default + Add<Output = Cost>,
+    GoalF: Fn(&State) -> bool,
+    ExpandF: Fn(&State) -> Vec<(State, Cost)>,
+{
+    astar(start, is_goal, expand, |_| Cost::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bfs() {
+        // 0 - 1 - 3, 0 - 2 - 3, 4 unreachable
+        let edges = [(0, 1), (0, 2), (1, 3), (2, 3), (4, 4)];
+        let expand = |n: &usize| {
+            edges
+                .iter()
+                .filter_map(|&(a, b)| {
+                    if *n == a {
+                        Some(b)
+                    } else if *n == b {
+                        Some(a)
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        };
+
+        let result = bfs(0usize, expand);
+        assert_eq!(
+            result,
+            vec![(0, None), (1, Some(0)), (2, Some(0)), (3, Some(1))]
+        );
+    }
+
+    #[test]
+    fn test_weighted_search() {
+        // 0 -1-> 1 -1-> 3 is cheaper than the direct 0 -5-> 3, 4 is unreachable
+        let edges = [
+            (0, 1, 1),
+            (1, 3, 1),
+            (0, 3, 5),
+            (0, 2, 1),
+            (2, 3, 3),
+            (4, 3, 1),
+        ];
+        let expand = |n: &usize| {
+            edges
+                .iter()
+                .filter(|&&(a, _, _)| a == *n)
+                .map(|&(_, b, c)| (b, c))
+                .collect()
+        };
+
+        assert_eq!(
+            dijkstra(0usize, |&n| n == 3, expand),
+            Some((2u32, vec![0, 1, 3]))
+        );
+        // Admissible heuristic, distance lower bounds towards 3
+        let heuristic = |n: &usize| [2u32, 1, 3, 0, 1][*n];
+        assert_eq!(
+            astar(0usize, |&n| n == 3, expand, heuristic),
+            Some((2u32, vec![0, 1, 3]))
+        );
+        assert_eq!(dijkstra(0usize, |&n| n == 4, expand), None::<(u32, _)>);
+        assert_eq!(dijkstra(3usize, |&n| n == 3, expand), Some((0u32, vec![3])));
+    }
+}
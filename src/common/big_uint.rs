@@ -0,0 +1,326 @@
+use crate::common::error::CommonError;
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, AddAssign, Mul, MulAssign};
+use std::str::FromStr;
+
+const LIMB_BITS: u32 = 32;
+const DECIMAL_CHUNK: u64 = 1_000_000_000;
+
+/// Minimal arbitrary precision unsigned integer, stored as little-endian 32-bit limbs.
+/// The limb vector never has trailing zeroes, so zero is represented by an empty vector.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct BigUint {
+    limbs: Vec<u32>,
+}
+
+impl BigUint {
+    pub fn zero() -> Self {
+        BigUint { limbs: Vec::new() }
+    }
+
+    pub fn one() -> Self {
+        BigUint::from(1u64)
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Builds a number from bits given in most significant first order.
+    pub fn from_bits<I: IntoIterator<Item = bool>>(bits: I) -> Self {
+        let mut result = BigUint::zero();
+        for bit in bits {
+            result.shl1_or(bit);
+        }
+        result
+    }
+
+    /// Number of significant bits.
+    pub fn bits(&self) -> usize {
+        match self.limbs.last() {
+            None => 0,
+            Some(last) => {
+                (self.limbs.len() - 1) * LIMB_BITS as usize + (32 - last.leading_zeros()) as usize
+            }
+        }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.limbs.len() > 2 {
+            return None;
+        }
+        Some(
+            self.limbs
+                .iter()
+                .rev()
+                .fold(0u64, |acc, &limb| (acc << LIMB_BITS) | limb as u64),
+        )
+    }
+
+    pub fn to_u128(&self) -> Option<u128> {
+        if self.limbs.len() > 4 {
+            return None;
+        }
+        Some(
+            self.limbs
+                .iter()
+                .rev()
+                .fold(0u128, |acc, &limb| (acc << LIMB_BITS) | limb as u128),
+        )
+    }
+
+    /// Remainder of division by a small divisor.
+    pub fn rem_u64(&self, divisor: u64) -> u64 {
+        assert_ne!(divisor, 0, "Division by zero");
+        self.limbs.iter().rev().fold(0u64, |acc, &limb| {
+            (((acc as u128) << LIMB_BITS | limb as u128) % divisor as u128) as u64
+        })
+    }
+
+    // Shifts left by one and sets the lowest bit, which is all that bit-by-bit parsing needs.
+    fn shl1_or(&mut self, bit: bool) {
+        let mut carry = bit as u32;
+        for limb in self.limbs.iter_mut() {
+            let next_carry = *limb >> (LIMB_BITS - 1);
+            *limb = (*limb << 1) | carry;
+            carry = next_carry;
+        }
+        if carry != 0 {
+            self.limbs.push(carry);
+        }
+    }
+
+    fn mul_small_add(&mut self, multiplier: u32, addend: u32) {
+        let mut carry = addend as u64;
+        for limb in self.limbs.iter_mut() {
+            let cur = *limb as u64 * multiplier as u64 + carry;
+            *limb = cur as u32;
+            carry = cur >> LIMB_BITS;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+        self.normalize();
+    }
+
+    fn div_rem_small(&self, divisor: u32) -> (BigUint, u32) {
+        let mut quotient = vec![0u32; self.limbs.len()];
+        let mut rem = 0u64;
+        for (i, &limb) in self.limbs.iter().enumerate().rev() {
+            let cur = (rem << LIMB_BITS) | limb as u64;
+            quotient[i] = (cur / divisor as u64) as u32;
+            rem = cur % divisor as u64;
+        }
+        let mut quotient = BigUint { limbs: quotient };
+        quotient.normalize();
+        (quotient, rem as u32)
+    }
+
+    fn normalize(&mut self) {
+        while let Some(&0) = self.limbs.last() {
+            self.limbs.pop();
+        }
+    }
+}
+
+impl From<u64> for BigUint {
+    fn from(value: u64) -> Self {
+        let mut result = BigUint {
+            limbs: vec![value as u32, (value >> LIMB_BITS) as u32],
+        };
+        result.normalize();
+        result
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(value: u128) -> Self {
+        let mut result = BigUint {
+            limbs: (0..4).map(|i| (value >> (i * LIMB_BITS)) as u32).collect(),
+        };
+        result.normalize();
+        result
+    }
+}
+
+impl Ord for BigUint {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.limbs
+            .len()
+            .cmp(&other.limbs.len())
+            .then_with(|| self.limbs.iter().rev().cmp(other.limbs.iter().rev()))
+    }
+}
+
+impl PartialOrd for BigUint {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for &BigUint {
+    type Output = BigUint;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let (longer, shorter) = if self.limbs.len() >= rhs.limbs.len() {
+            (self, rhs)
+        } else {
+            (rhs, self)
+        };
+
+        let mut limbs = Vec::with_capacity(longer.limbs.len() + 1);
+        let mut carry = 0u64;
+        for (i, &limb) in longer.limbs.iter().enumerate() {
+            let cur = limb as u64 + *shorter.limbs.get(i).unwrap_or(&0) as u64 + carry;
+            limbs.push(cur as u32);
+            carry = cur >> LIMB_BITS;
+        }
+        if carry != 0 {
+            limbs.push(carry as u32);
+        }
+        BigUint { limbs }
+    }
+}
+
+impl Add for BigUint {
+    type Output = BigUint;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        &self + &rhs
+    }
+}
+
+impl AddAssign<&BigUint> for BigUint {
+    fn add_assign(&mut self, rhs: &BigUint) {
+        *self = &*self + rhs;
+    }
+}
+
+impl Mul for &BigUint {
+    type Output = BigUint;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        if self.is_zero() || rhs.is_zero() {
+            return BigUint::zero();
+        }
+
+        let mut limbs = vec![0u32; self.limbs.len() + rhs.limbs.len()];
+        for (i, &a) in self.limbs.iter().enumerate() {
+            let mut carry = 0u64;
+            for (j, &b) in rhs.limbs.iter().enumerate() {
+                let cur = a as u64 * b as u64 + limbs[i + j] as u64 + carry;
+                limbs[i + j] = cur as u32;
+                carry = cur >> LIMB_BITS;
+            }
+            limbs[i + rhs.limbs.len()] = carry as u32;
+        }
+
+        let mut result = BigUint { limbs };
+        result.normalize();
+        result
+    }
+}
+
+impl Mul for BigUint {
+    type Output = BigUint;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        &self * &rhs
+    }
+}
+
+impl MulAssign<&BigUint> for BigUint {
+    fn mul_assign(&mut self, rhs: &BigUint) {
+        *self = &*self * rhs;
+    }
+}
+
+impl Display for BigUint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+
+        // Peel off base 10^9 chunks, least significant first
+        let mut chunks = Vec::new();
+        let mut cur = self.clone();
+        while !cur.is_zero() {
+            let (quotient, rem) = cur.div_rem_small(DECIMAL_CHUNK as u32);
+            chunks.push(rem);
+            cur = quotient;
+        }
+
+        let mut s = chunks.last().unwrap().to_string();
+        for chunk in chunks.iter().rev().skip(1) {
+            s.push_str(&format!("{:09}", chunk));
+        }
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() {
+            return Err(CommonError::Parse("Empty string while parsing BigUint."));
+        }
+
+        let mut result = BigUint::zero();
+        for ch in s.chars() {
+            let digit = ch.to_digit(10).ok_or(CommonError::Parse(
+                "Non-digit character while parsing BigUint.",
+            ))?;
+            result.mul_small_add(10, digit);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(BigUint::from(0u64), BigUint::zero());
+        assert_eq!(BigUint::from(u64::MAX).to_u64(), Some(u64::MAX));
+        assert_eq!(BigUint::from(u128::MAX).to_u128(), Some(u128::MAX));
+        assert_eq!(BigUint::from(u128::MAX).to_u64(), None);
+        assert_eq!(
+            BigUint::from_bits([true, false, true, true]).to_u64(),
+            Some(11)
+        );
+        assert_eq!(BigUint::from(11u64).bits(), 4);
+        assert_eq!(BigUint::zero().bits(), 0);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let a = BigUint::from(u64::MAX);
+        let b = BigUint::from(u64::MAX);
+        assert_eq!(
+            (&a * &b).to_u128(),
+            Some(u64::MAX as u128 * u64::MAX as u128)
+        );
+        assert_eq!((&a + &b).to_u128(), Some(2 * u64::MAX as u128));
+        assert_eq!(&a * &BigUint::zero(), BigUint::zero());
+        assert!(a < &a + &BigUint::one());
+        assert_eq!((&a * &b).rem_u64(1_000_000_007), {
+            let m = 1_000_000_007u128;
+            ((u64::MAX as u128 % m) * (u64::MAX as u128 % m) % m) as u64
+        });
+    }
+
+    #[test]
+    fn test_display_parse() {
+        let s = "340282366920938463463374607431768211456"; // 2^128
+        let n: BigUint = s.parse().unwrap();
+        assert_eq!(n, &BigUint::from(u128::MAX) + &BigUint::one());
+        assert_eq!(n.to_string(), s);
+        assert_eq!(BigUint::zero().to_string(), "0");
+        assert_eq!(BigUint::from(1_000_000_000u64).to_string(), "1000000000");
+        assert!("12a".parse::<BigUint>().is_err());
+    }
+}
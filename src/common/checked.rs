@@ -0,0 +1,111 @@
+use crate::common::big_uint::BigUint;
+
+/// Integer types whose arithmetic reports overflow, so callers can turn it into an error instead
+/// of getting a silently wrapped result.
+pub trait CheckedArithmetic: Sized {
+    fn checked_add(&self, other: &Self) -> Option<Self>;
+    fn checked_mul(&self, other: &Self) -> Option<Self>;
+}
+
+impl CheckedArithmetic for u64 {
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u64::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        u64::checked_mul(*self, *other)
+    }
+}
+
+impl CheckedArithmetic for u128 {
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        u128::checked_add(*self, *other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        u128::checked_mul(*self, *other)
+    }
+}
+
+/// Never overflows.
+impl CheckedArithmetic for BigUint {
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self * other)
+    }
+}
+
+/// Residue modulo a modulus chosen at runtime. Never overflows, but both operands need the same
+/// modulus.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Modular {
+    value: u64,
+    modulus: u64,
+}
+
+impl Modular {
+    pub fn new(value: u64, modulus: u64) -> Self {
+        assert!(modulus > 0, "Modulus has to be positive");
+        Modular {
+            value: value % modulus,
+            modulus,
+        }
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    pub fn modulus(&self) -> u64 {
+        self.modulus
+    }
+
+    fn combine(&self, other: &Self, op: fn(u128, u128) -> u128) -> Self {
+        assert_eq!(self.modulus, other.modulus, "Mixed moduli");
+        let value = op(self.value as u128, other.value as u128) % self.modulus as u128;
+        Modular {
+            value: value as u64,
+            modulus: self.modulus,
+        }
+    }
+}
+
+impl CheckedArithmetic for Modular {
+    fn checked_add(&self, other: &Self) -> Option<Self> {
+        Some(self.combine(other, |a, b| a + b))
+    }
+
+    fn checked_mul(&self, other: &Self) -> Option<Self> {
+        Some(self.combine(other, |a, b| a * b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checked_arithmetic() {
+        assert_eq!(CheckedArithmetic::checked_add(&u64::MAX, &1), None);
+        assert_eq!(
+            CheckedArithmetic::checked_mul(&(1u128 << 64), &(1 << 63)),
+            Some(1 << 127)
+        );
+        assert_eq!(
+            CheckedArithmetic::checked_mul(&(1u128 << 64), &(1 << 64)),
+            None
+        );
+
+        let big = BigUint::from(u128::MAX);
+        assert_eq!(big.checked_mul(&big).unwrap().bits(), 256);
+
+        let a = Modular::new(20, 7);
+        assert_eq!(a.value(), 6);
+        assert_eq!(a.checked_add(&Modular::new(3, 7)), Some(Modular::new(2, 7)));
+        let huge = Modular::new(u64::MAX - 1, u64::MAX);
+        assert_eq!(huge.checked_mul(&huge).unwrap().value(), 1);
+    }
+}
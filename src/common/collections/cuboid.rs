@@ -0,0 +1,127 @@
+use crate::common::collections::interval::Interval;
+use crate::common::collections::vec3d::Vec3di;
+
+/// Axis-aligned box of lattice points, inclusive on both corners.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Cuboid {
+    pub min: Vec3di,
+    pub max: Vec3di,
+}
+
+impl Cuboid {
+    pub fn new(min: Vec3di, max: Vec3di) -> Self {
+        assert!(
+            min.x <= max.x && min.y <= max.y && min.z <= max.z,
+            "Cuboid min corner is beyond its max corner"
+        );
+        Cuboid { min, max }
+    }
+
+    pub fn from_intervals(x: Interval<i64>, y: Interval<i64>, z: Interval<i64>) -> Self {
+        Cuboid {
+            min: Vec3di::new(x.start, y.start, z.start),
+            max: Vec3di::new(x.end, y.end, z.end),
+        }
+    }
+
+    /// Extent along the given axis, 0 for x, 1 for y and 2 for z.
+    pub fn axis(&self, axis: usize) -> Interval<i64> {
+        Interval::new(self.min[axis], self.max[axis])
+    }
+
+    /// Number of lattice points inside.
+    pub fn volume(&self) -> i64 {
+        (0..3).map(|axis| self.axis(axis).len()).product()
+    }
+
+    pub fn contains(&self, point: &Vec3di) -> bool {
+        (0..3).all(|axis| self.axis(axis).contains(point[axis]))
+    }
+
+    pub fn contains_cuboid(&self, other: &Cuboid) -> bool {
+        self.contains(&other.min) && self.contains(&other.max)
+    }
+
+    pub fn intersection(&self, other: &Cuboid) -> Option<Cuboid> {
+        let x = self.axis(0).intersection(&other.axis(0))?;
+        let y = self.axis(1).intersection(&other.axis(1))?;
+        let z = self.axis(2).intersection(&other.axis(2))?;
+        Some(Cuboid::from_intervals(x, y, z))
+    }
+
+    /// Splits the part of `self` outside `other` into at most six disjoint cuboids.
+    /// Slabs are cut off along x first, then y, then z, each from what is left of the previous
+    /// axis, so the pieces never overlap.
+    pub fn subtract(&self, other: &Cuboid) -> Vec<Cuboid> {
+        let common = match self.intersection(other) {
+            None => return vec![*self],
+            Some(common) => common,
+        };
+
+        let mut result = Vec::with_capacity(6);
+        let mut remaining = *self;
+        for axis in 0..3 {
+            for part in remaining.axis(axis).difference(&common.axis(axis)) {
+                let mut piece = remaining;
+                piece.min[axis] = part.start;
+                piece.max[axis] = part.end;
+                result.push(piece);
+            }
+            remaining.min[axis] = common.min[axis];
+            remaining.max[axis] = common.max[axis];
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(min: (i64, i64, i64), max: (i64, i64, i64)) -> Cuboid {
+        Cuboid::new(
+            Vec3di::new(min.0, min.1, min.2),
+            Vec3di::new(max.0, max.1, max.2),
+        )
+    }
+
+    #[test]
+    fn test_volume_intersection() {
+        let a = cube((10, 10, 10), (12, 12, 12));
+        let b = cube((11, 11, 11), (13, 13, 13));
+
+        assert_eq!(a.volume(), 27);
+        assert_eq!(a.intersection(&b), Some(cube((11, 11, 11), (12, 12, 12))));
+        assert_eq!(a.intersection(&cube((13, 10, 10), (14, 12, 12))), None);
+        assert!(a.contains(&Vec3di::new(12, 10, 11)));
+        assert!(!a.contains(&Vec3di::new(13, 10, 11)));
+        assert!(a.contains_cuboid(&cube((11, 11, 11), (12, 12, 12))));
+    }
+
+    #[test]
+    fn test_subtract() {
+        let a = cube((10, 10, 10), (12, 12, 12));
+        let b = cube((11, 11, 11), (13, 13, 13));
+
+        let pieces = a.subtract(&b);
+        assert_eq!(pieces.iter().map(Cuboid::volume).sum::<i64>(), 27 - 8);
+        for (i, p) in pieces.iter().enumerate() {
+            assert!(a.contains_cuboid(p));
+            assert_eq!(p.intersection(&b), None);
+            for q in pieces.iter().skip(i + 1) {
+                assert_eq!(p.intersection(q), None);
+            }
+        }
+
+        // A hole in the middle leaves six slabs
+        let big = cube((0, 0, 0), (4, 4, 4));
+        let hole = cube((1, 1, 1), (3, 3, 3));
+        let pieces = big.subtract(&hole);
+        assert_eq!(pieces.len(), 6);
+        assert_eq!(pieces.iter().map(Cuboid::volume).sum::<i64>(), 125 - 27);
+
+        assert_eq!(a.subtract(&cube((20, 20, 20), (21, 21, 21))), vec![a]);
+        assert_eq!(a.subtract(&big.subtract(&hole)[0]), vec![a]);
+        assert!(hole.subtract(&big).is_empty());
+    }
+}
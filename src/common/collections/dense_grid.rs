@@ -167,6 +167,18 @@ impl FromStr for ArrayGrid<i32> {
     }
 }
 
+/// Inverse of parsing, one line per row without a trailing newline.
+impl Display for ArrayGrid<char> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let lines: Vec<String> = self
+            .underlying
+            .chunks(self.x_size)
+            .map(|line| line.iter().collect())
+            .collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
 /// Best effort
 impl Display for ArrayGrid<i32> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -217,6 +229,7 @@ mod tests {
         assert_eq!(*array_grid.get_pos(2, 1), '4');
         assert_eq!(*array_grid.get_pos(6, 0), '7');
         assert_eq!(*array_grid.get_pos(6, 1), '8');
+        assert_eq!(array_grid.to_string(), data);
     }
 
     #[test]
@@ -0,0 +1,272 @@
+use crate::common::collections::vector::Scalar;
+use crate::common::error::CommonError;
+use std::str::FromStr;
+
+/// Inclusive range `start..=end`, never empty.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Interval<T> {
+    pub start: T,
+    pub end: T,
+}
+
+impl<T: Scalar> Interval<T> {
+    pub fn new(start: T, end: T) -> Self {
+        assert!(start <= end, "Interval start is after its end");
+        Interval { start, end }
+    }
+
+    /// Number of integer points in the interval.
+    pub fn len(&self) -> T {
+        self.end - self.start + T::one()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    pub fn contains_interval(&self, other: &Self) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let start = self.start.max_of(other.start);
+        let end = self.end.min_of(other.end);
+        if start <= end {
+            Some(Interval { start, end })
+        } else {
+            None
+        }
+    }
+
+    /// Parts of `self` not covered by `other`, at most two of them.
+    pub fn difference(&self, other: &Self) -> Vec<Self> {
+        match self.intersection(other) {
+            None => vec![*self],
+            Some(common) => {
+                let mut result = Vec::with_capacity(2);
+                if self.start < common.start {
+                    result.push(Interval::new(self.start, common.start - T::one()));
+                }
+                if common.end < self.end {
+                    result.push(Interval::new(common.end + T::one(), self.end));
+                }
+                result
+            }
+        }
+    }
+
+    /// Widens the interval by `by` on both ends.
+    pub fn grow(&self, by: T) -> Self {
+        Interval::new(self.start - by, self.end + by)
+    }
+}
+
+/// Parses `a..b`, as used in puzzle inputs like `x=20..30`.
+impl<T: Scalar + FromStr> FromStr for Interval<T> {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .trim()
+            .split_once("..")
+            .ok_or(CommonError::Parse("Interval has to be in the form a..b."))?;
+        let start: T = start
+            .parse()
+            .map_err(|_| CommonError::Parse("Malformed interval start."))?;
+        let end: T = end
+            .parse()
+            .map_err(|_| CommonError::Parse("Malformed interval end."))?;
+
+        if start > end {
+            Err(CommonError::Parse("Interval start is after its end."))
+        } else {
+            Ok(Interval::new(start, end))
+        }
+    }
+}
+
+/// Union of inclusive intervals, kept as a sorted list of disjoint and non-adjacent intervals.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct IntervalSet<T> {
+    intervals: Vec<Interval<T>>,
+}
+
+impl<T: Scalar + Ord> IntervalSet<T> {
+    pub fn new() -> Self {
+        IntervalSet {
+            intervals: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// Total number of integer points covered.
+    pub fn len(&self) -> T {
+        self.intervals
+            .iter()
+            .fold(T::default(), |acc, interval| acc + interval.len())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Interval<T>> {
+        self.intervals.iter()
+    }
+
+    pub fn contains(&self, value: T) -> bool {
+        // Last interval starting at or before the value is the only candidate
+        let idx = self.intervals.partition_point(|i| i.start <= value);
+        idx > 0 && self.intervals[idx - 1].contains(value)
+    }
+
+    pub fn insert(&mut self, interval: Interval<T>) {
+        self.intervals.push(interval);
+        self.normalize();
+    }
+
+    pub fn union(&self, other: &Self) -> Self {
+        let mut intervals = self.intervals.clone();
+        intervals.extend(other.intervals.iter().cloned());
+        let mut result = IntervalSet { intervals };
+        result.normalize();
+        result
+    }
+
+    pub fn intersection(&self, other: &Self) -> Self {
+        // Both lists are sorted, so a merge-style sweep visits each pair that can overlap
+        let mut intervals = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (&self.intervals[i], &other.intervals[j]);
+            if let Some(common) = a.intersection(b) {
+                intervals.push(common);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        IntervalSet { intervals }
+    }
+
+    pub fn difference(&self, other: &Self) -> Self {
+        let mut intervals = Vec::new();
+        for interval in &self.intervals {
+            let mut remaining = vec![*interval];
+            for removed in other
+                .intervals
+                .iter()
+                .filter(|r| r.intersection(interval).is_some())
+            {
+                remaining = remaining
+                    .iter()
+                    .flat_map(|part| part.difference(removed))
+                    .collect();
+            }
+            intervals.extend(remaining);
+        }
+        IntervalSet { intervals }
+    }
+
+    // Sorts and merges overlapping or touching intervals
+    fn normalize(&mut self) {
+        self.intervals.sort();
+        let mut merged: Vec<Interval<T>> = Vec::with_capacity(self.intervals.len());
+        for interval in self.intervals.drain(..) {
+            match merged.last_mut() {
+                Some(last) if interval.start <= last.end + T::one() => {
+                    last.end = last.end.max_of(interval.end);
+                }
+                _ => merged.push(interval),
+            }
+        }
+        self.intervals = merged;
+    }
+}
+
+impl<T: Scalar + Ord> FromIterator<Interval<T>> for IntervalSet<T> {
+    fn from_iter<I: IntoIterator<Item = Interval<T>>>(iter: I) -> Self {
+        let mut result = IntervalSet {
+            intervals: iter.into_iter().collect(),
+        };
+        result.normalize();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interval() {
+        let a = Interval::new(1i64, 10);
+        let b = Interval::new(5i64, 15);
+
+        assert_eq!(a.len(), 10);
+        assert!(a.contains(10) && !a.contains(11));
+        assert_eq!(a.intersection(&b), Some(Interval::new(5, 10)));
+        assert_eq!(a.intersection(&Interval::new(11, 12)), None);
+        assert_eq!(a.difference(&b), vec![Interval::new(1, 4)]);
+        assert_eq!(
+            a.difference(&Interval::new(3, 4)),
+            vec![Interval::new(1, 2), Interval::new(5, 10)]
+        );
+        assert_eq!(a.difference(&Interval::new(-5, 20)), vec![]);
+        assert_eq!(a.grow(2), Interval::new(-1, 12));
+
+        assert_eq!(
+            "-5..3".parse::<Interval<i64>>().unwrap(),
+            Interval::new(-5, 3)
+        );
+        assert!("3..-5".parse::<Interval<i64>>().is_err());
+        assert!("3-5".parse::<Interval<i64>>().is_err());
+    }
+
+    #[test]
+    fn test_interval_set() {
+        let a: IntervalSet<i64> = [
+            Interval::new(1, 3),
+            Interval::new(10, 12),
+            Interval::new(4, 5),
+        ]
+        .into_iter()
+        .collect();
+        // Adjacent intervals merge
+        assert_eq!(
+            a.iter().cloned().collect::<Vec<_>>(),
+            vec![Interval::new(1, 5), Interval::new(10, 12)]
+        );
+        assert_eq!(a.len(), 8);
+        assert!(a.contains(5) && !a.contains(7) && a.contains(12) && !a.contains(0));
+
+        let b: IntervalSet<i64> = [Interval::new(3, 11)].into_iter().collect();
+        assert_eq!(
+            a.union(&b).iter().cloned().collect::<Vec<_>>(),
+            vec![Interval::new(1, 12)]
+        );
+        assert_eq!(
+            a.intersection(&b).iter().cloned().collect::<Vec<_>>(),
+            vec![Interval::new(3, 5), Interval::new(10, 11)]
+        );
+        assert_eq!(
+            a.difference(&b).iter().cloned().collect::<Vec<_>>(),
+            vec![Interval::new(1, 2), Interval::new(12, 12)]
+        );
+        assert_eq!(
+            b.difference(&a).iter().cloned().collect::<Vec<_>>(),
+            vec![Interval::new(6, 9)]
+        );
+
+        let mut c = IntervalSet::new();
+        assert!(c.is_empty());
+        c.insert(Interval::new(0usize, 0));
+        c.insert(Interval::new(2usize, 2));
+        c.insert(Interval::new(1usize, 1));
+        assert_eq!(
+            c.iter().cloned().collect::<Vec<_>>(),
+            vec![Interval::new(0, 2)]
+        );
+    }
+}
@@ -1,6 +1,9 @@
+pub mod cuboid;
 pub mod dense_grid;
+pub mod interval;
 pub mod sparse_grid;
 pub mod vec3d;
+pub mod vector;
 
 pub const NEIGHBOURS_CROSS_2D: [(i32, i32); 4] = [(0, -1), (1, 0), (0, 1), (-1, 0)];
 pub const NEIGHBOURS_2D: [(i32, i32); 8] = [
@@ -1,162 +1,186 @@
+use crate::common::collections::vector::{Matrix3, Scalar, Vec3};
 use crate::common::error::CommonError;
-use std::ops::{Add, AddAssign, Index, IndexMut, Neg, Sub, SubAssign};
-use std::str::FromStr;
 
 pub trait Vec3d<T> {
     fn new(x: T, y: T, z: T) -> Self;
     fn norm_l1(&self) -> f64;
     fn norm_l2(&self) -> f64;
     fn distance_l2(&self, other: &Self) -> f64;
+    fn distance_l2_squared(&self, other: &Self) -> T;
 }
 
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
-pub struct Vec3di {
-    pub x: i64,
-    pub y: i64,
-    pub z: i64,
-}
-
-impl Add for Vec3di {
-    type Output = Vec3di;
+pub type Vec3di = Vec3<i64>;
 
-    fn add(self, rhs: Self) -> Self::Output {
-        Self {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
-        }
+impl<T: Scalar> Vec3d<T> for Vec3<T> {
+    fn new(x: T, y: T, z: T) -> Self {
+        Vec3 { x, y, z }
     }
-}
 
-impl Add for &Vec3di {
-    type Output = Vec3di;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        Vec3di {
-            x: self.x + rhs.x,
-            y: self.y + rhs.y,
-            z: self.z + rhs.z,
-        }
+    fn norm_l1(&self) -> f64 {
+        self.manhattan_norm().to_f64()
     }
-}
 
-impl AddAssign for Vec3di {
-    fn add_assign(&mut self, rhs: Self) {
-        self.x += rhs.x;
-        self.y += rhs.y;
-        self.z += rhs.z;
+    fn norm_l2(&self) -> f64 {
+        self.squared_norm().to_f64().sqrt()
     }
-}
 
-impl Sub for Vec3di {
-    type Output = Vec3di;
+    fn distance_l2(&self, other: &Self) -> f64 {
+        self.distance_l2_squared(other).to_f64().sqrt()
+    }
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        Self {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
-            z: self.z - rhs.z,
+    fn distance_l2_squared(&self, other: &Self) -> T {
+        let mut sum = T::default();
+        for i in 0..3 {
+            let diff = self[i].abs_diff(other[i]);
+            sum = sum + diff * diff;
         }
+        sum
     }
 }
 
-impl Sub for &Vec3di {
-    type Output = Vec3di;
+/// Rotation of the integer lattice by multiples of 90 degrees, stored as a signed permutation
+/// matrix. Only proper rotations (determinant +1) can be constructed, so mirrored frames are
+/// never produced.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+pub struct Rotation {
+    matrix: Matrix3<i64>,
+}
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        Vec3di {
-            x: self.x - rhs.x,
-            y: self.y - rhs.y,
-            z: self.z - rhs.z,
+impl Rotation {
+    pub fn identity() -> Self {
+        Rotation {
+            matrix: Matrix3::identity(),
         }
     }
-}
-impl SubAssign for Vec3di {
-    fn sub_assign(&mut self, rhs: Self) {
-        self.x -= rhs.x;
-        self.y -= rhs.y;
-        self.z -= rhs.z;
-    }
-}
 
-impl Neg for Vec3di {
-    type Output = Vec3di;
+    /// Accepts signed permutation matrices with determinant +1.
+    pub fn from_matrix(matrix: [[i64; 3]; 3]) -> Result<Self, CommonError> {
+        let is_signed_permutation = (0..3).all(|i| {
+            let row_nonzero = (0..3).filter(|&j| matrix[i][j] != 0).count();
+            let col_nonzero = (0..3).filter(|&j| matrix[j][i] != 0).count();
+            row_nonzero == 1 && col_nonzero == 1 && matrix[i].iter().all(|e| e.abs() <= 1)
+        });
+
+        if !is_signed_permutation {
+            Err(CommonError::Parse(
+                "Rotation matrix has to be a signed permutation.",
+            ))
+        } else if Matrix3::new(matrix).determinant() != 1 {
+            Err(CommonError::Parse(
+                "Rotation matrix is a reflection, not a proper rotation.",
+            ))
+        } else {
+            Ok(Rotation {
+                matrix: Matrix3::new(matrix),
+            })
+        }
+    }
 
-    fn neg(self) -> Self::Output {
-        Vec3di {
-            x: -self.x,
-            y: -self.y,
-            z: -self.z,
+    /// All 24 proper rotations, starting with the identity.
+    pub fn all() -> Vec<Rotation> {
+        const PERMUTATIONS: [[usize; 3]; 6] = [
+            [0, 1, 2],
+            [0, 2, 1],
+            [1, 0, 2],
+            [1, 2, 0],
+            [2, 0, 1],
+            [2, 1, 0],
+        ];
+
+        let mut result = Vec::with_capacity(24);
+        for permutation in PERMUTATIONS {
+            for signs in 0..8 {
+                let mut matrix = [[0i64; 3]; 3];
+                for (row, &col) in permutation.iter().enumerate() {
+                    matrix[row][col] = if signs & (1 << row) == 0 { 1 } else { -1 };
+                }
+                if let Ok(rotation) = Rotation::from_matrix(matrix) {
+                    result.push(rotation);
+                }
+            }
         }
+        result
     }
-}
 
-impl Vec3d<i64> for Vec3di {
-    fn new(x: i64, y: i64, z: i64) -> Self {
-        Vec3di { x, y, z }
+    pub fn matrix(&self) -> &Matrix3<i64> {
+        &self.matrix
     }
 
-    fn norm_l1(&self) -> f64 {
-        (self.x.abs() + self.y.abs() + self.z.abs()) as f64
+    pub fn apply(&self, v: &Vec3di) -> Vec3di {
+        self.matrix * *v
     }
 
-    fn norm_l2(&self) -> f64 {
-        ((self.x.pow(2) + self.y.pow(2) + self.z.pow(2)) as f64).sqrt()
+    /// Rotation that applies `other` first, then `self`.
+    pub fn compose(&self, other: &Rotation) -> Rotation {
+        Rotation {
+            matrix: self.matrix * other.matrix,
+        }
     }
 
-    fn distance_l2(&self, other: &Self) -> f64 {
-        (((self.x - other.x).pow(2) + (self.y - other.y).pow(2) + (self.z - other.z).pow(2)) as f64)
-            .sqrt()
+    /// Rotation matrices are orthogonal, so the inverse is the transpose.
+    pub fn inverse(&self) -> Rotation {
+        Rotation {
+            matrix: self.matrix.transpose(),
+        }
     }
 }
 
-impl Index<usize> for Vec3di {
-    type Output = i64;
-
-    fn index(&self, index: usize) -> &Self::Output {
-        match index {
-            0 => &self.x,
-            1 => &self.y,
-            2 => &self.z,
-            _ => panic!("Index out of bounds: {}", index),
-        }
+impl Default for Rotation {
+    fn default() -> Self {
+        Self::identity()
     }
 }
 
-impl IndexMut<usize> for Vec3di {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        match index {
-            0 => &mut self.x,
-            1 => &mut self.y,
-            2 => &mut self.z,
-            _ => panic!("Index out of bounds: {}", index),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_rotation_group() {
+        let rotations = Rotation::all();
+        assert_eq!(rotations.len(), 24);
+        assert_eq!(rotations[0], Rotation::identity());
+
+        let unique: BTreeSet<Rotation> = rotations.iter().cloned().collect();
+        assert_eq!(unique.len(), 24);
+
+        for a in &rotations {
+            assert_eq!(a.matrix().determinant(), 1);
+            assert_eq!(a.compose(&a.inverse()), Rotation::identity());
+            for b in &rotations {
+                assert!(unique.contains(&a.compose(b)));
+            }
         }
+
+        // Every rotation moves a generic vector to a distinct place
+        let v = Vec3di::new(1, 2, 3);
+        let images: BTreeSet<Vec3di> = rotations.iter().map(|r| r.apply(&v)).collect();
+        assert_eq!(images.len(), 24);
+    }
+
+    #[test]
+    fn test_rotation_apply_compose() {
+        // 90 degrees around z: x -> y, y -> -x
+        let rot_z = Rotation::from_matrix([[0, -1, 0], [1, 0, 0], [0, 0, 1]]).unwrap();
+        // 90 degrees around x: y -> z, z -> -y
+        let rot_x = Rotation::from_matrix([[1, 0, 0], [0, 0, -1], [0, 1, 0]]).unwrap();
+
+        let v = Vec3di::new(1, 2, 3);
+        assert_eq!(rot_z.apply(&v), Vec3di::new(-2, 1, 3));
+        assert_eq!(rot_x.apply(&v), Vec3di::new(1, -3, 2));
+        assert_eq!(
+            rot_x.compose(&rot_z).apply(&v),
+            rot_x.apply(&rot_z.apply(&v))
+        );
+        assert_eq!(rot_z.inverse().apply(&rot_z.apply(&v)), v);
     }
-}
 
-impl FromStr for Vec3di {
-    type Err = CommonError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut iter = s.split(',');
-        let x = iter
-            .next()
-            .ok_or(CommonError::Parse(
-                "Malformed input for while parsing Vec3di.",
-            ))?
-            .parse()?;
-        let y = iter
-            .next()
-            .ok_or(CommonError::Parse(
-                "Malformed input for while parsing Vec3di.",
-            ))?
-            .parse()?;
-        let z = iter
-            .next()
-            .ok_or(CommonError::Parse(
-                "Malformed input for while parsing Vec3di.",
-            ))?
-            .parse()?;
-        Ok(Vec3di::new(x, y, z))
+    #[test]
+    fn test_rotation_rejects_improper() {
+        assert!(Rotation::from_matrix([[-1, 0, 0], [0, 1, 0], [0, 0, 1]]).is_err());
+        assert!(Rotation::from_matrix([[0, 1, 0], [1, 0, 0], [0, 0, 1]]).is_err());
+        assert!(Rotation::from_matrix([[1, 1, 0], [0, 1, 0], [0, 0, 1]]).is_err());
+        assert!(Rotation::from_matrix([[2, 0, 0], [0, 1, 0], [0, 0, 1]]).is_err());
     }
 }
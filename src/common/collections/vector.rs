@@ -0,0 +1,477 @@
+use crate::common::error::CommonError;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Neg, Sub, SubAssign,
+};
+use std::str::FromStr;
+
+/// Numeric types usable as vector and matrix components.
+pub trait Scalar:
+    Copy
+    + PartialOrd
+    + Default
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+{
+    fn one() -> Self;
+    fn to_f64(self) -> f64;
+
+    /// Absolute difference, which also works for unsigned types.
+    fn abs_diff(self, other: Self) -> Self {
+        if self > other {
+            self - other
+        } else {
+            other - self
+        }
+    }
+
+    fn min_of(self, other: Self) -> Self {
+        if other < self {
+            other
+        } else {
+            self
+        }
+    }
+
+    fn max_of(self, other: Self) -> Self {
+        if other > self {
+            other
+        } else {
+            self
+        }
+    }
+}
+
+macro_rules! impl_scalar {
+    ($($t:ty => $one:expr),*) => {
+        $(
+            impl Scalar for $t {
+                fn one() -> Self {
+                    $one
+                }
+
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+            }
+        )*
+    };
+}
+
+impl_scalar!(i32 => 1, i64 => 1, isize => 1, u32 => 1, u64 => 1, usize => 1, f32 => 1.0, f64 => 1.0);
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct Vec2<T> {
+    pub x: T,
+    pub y: T,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct Vec3<T> {
+    pub x: T,
+    pub y: T,
+    pub z: T,
+}
+
+// Component-wise operators, shared by both vector types
+macro_rules! impl_vector_ops {
+    ($name:ident, $($c:ident),+) => {
+        impl<T: Scalar> Add for $name<T> {
+            type Output = $name<T>;
+
+            fn add(self, rhs: Self) -> Self::Output {
+                $name { $($c: self.$c + rhs.$c),+ }
+            }
+        }
+
+        impl<T: Scalar> Add for &$name<T> {
+            type Output = $name<T>;
+
+            fn add(self, rhs: Self) -> Self::Output {
+                *self + *rhs
+            }
+        }
+
+        impl<T: Scalar> AddAssign for $name<T> {
+            fn add_assign(&mut self, rhs: Self) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl<T: Scalar> Sub for $name<T> {
+            type Output = $name<T>;
+
+            fn sub(self, rhs: Self) -> Self::Output {
+                $name { $($c: self.$c - rhs.$c),+ }
+            }
+        }
+
+        impl<T: Scalar> Sub for &$name<T> {
+            type Output = $name<T>;
+
+            fn sub(self, rhs: Self) -> Self::Output {
+                *self - *rhs
+            }
+        }
+
+        impl<T: Scalar> SubAssign for $name<T> {
+            fn sub_assign(&mut self, rhs: Self) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl<T: Scalar + Neg<Output = T>> Neg for $name<T> {
+            type Output = $name<T>;
+
+            fn neg(self) -> Self::Output {
+                $name { $($c: -self.$c),+ }
+            }
+        }
+
+        impl<T: Scalar> Mul<T> for $name<T> {
+            type Output = $name<T>;
+
+            fn mul(self, rhs: T) -> Self::Output {
+                $name { $($c: self.$c * rhs),+ }
+            }
+        }
+
+        impl<T: Scalar> MulAssign<T> for $name<T> {
+            fn mul_assign(&mut self, rhs: T) {
+                *self = *self * rhs;
+            }
+        }
+
+        /// Component-wise division, integer types round towards zero.
+        impl<T: Scalar> Div<T> for $name<T> {
+            type Output = $name<T>;
+
+            fn div(self, rhs: T) -> Self::Output {
+                $name { $($c: self.$c / rhs),+ }
+            }
+        }
+
+        impl<T: Scalar> DivAssign<T> for $name<T> {
+            fn div_assign(&mut self, rhs: T) {
+                *self = *self / rhs;
+            }
+        }
+
+        impl<T: Scalar> $name<T> {
+            pub fn dot(&self, other: &Self) -> T {
+                let mut sum = T::default();
+                $(sum = sum + self.$c * other.$c;)+
+                sum
+            }
+
+            /// Component-wise minimum.
+            pub fn component_min(&self, other: &Self) -> Self {
+                $name { $($c: self.$c.min_of(other.$c)),+ }
+            }
+
+            /// Component-wise maximum.
+            pub fn component_max(&self, other: &Self) -> Self {
+                $name { $($c: self.$c.max_of(other.$c)),+ }
+            }
+
+            /// L1 distance.
+            pub fn manhattan_distance(&self, other: &Self) -> T {
+                let mut sum = T::default();
+                $(sum = sum + self.$c.abs_diff(other.$c);)+
+                sum
+            }
+
+            /// L-infinity distance.
+            pub fn chebyshev_distance(&self, other: &Self) -> T {
+                let mut result = T::default();
+                $(result = result.max_of(self.$c.abs_diff(other.$c));)+
+                result
+            }
+
+            pub fn manhattan_norm(&self) -> T {
+                self.manhattan_distance(&Self::default())
+            }
+
+            pub fn squared_norm(&self) -> T {
+                self.dot(self)
+            }
+        }
+    };
+}
+
+impl_vector_ops!(Vec2, x, y);
+impl_vector_ops!(Vec3, x, y, z);
+
+impl<T: Scalar> Vec2<T> {
+    pub fn new(x: T, y: T) -> Self {
+        Vec2 { x, y }
+    }
+
+    /// Z component of the 3D cross product, positive if `other` is counter-clockwise of `self`.
+    pub fn cross(&self, other: &Self) -> T {
+        self.x * other.y - self.y * other.x
+    }
+}
+
+impl<T: Scalar> Vec3<T> {
+    pub fn new(x: T, y: T, z: T) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    pub fn cross(&self, other: &Self) -> Self {
+        Vec3 {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+}
+
+impl<T> Index<usize> for Vec2<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            _ => panic!("Index out of bounds: {}", index),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Vec2<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            _ => panic!("Index out of bounds: {}", index),
+        }
+    }
+}
+
+impl<T> Index<usize> for Vec3<T> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        match index {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Index out of bounds: {}", index),
+        }
+    }
+}
+
+impl<T> IndexMut<usize> for Vec3<T> {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        match index {
+            0 => &mut self.x,
+            1 => &mut self.y,
+            2 => &mut self.z,
+            _ => panic!("Index out of bounds: {}", index),
+        }
+    }
+}
+
+// Splits "1,2,3", "1 2 3" or "1, 2, 3" into components, parsing the ones named in `names`.
+fn parse_components<T, const N: usize>(
+    s: &str,
+    names: [&'static str; N],
+) -> Result<[T; N], CommonError>
+where
+    T: FromStr + Default + Copy,
+{
+    let mut iter = s
+        .split(|ch: char| ch == ',' || ch.is_whitespace())
+        .filter(|chunk| !chunk.is_empty());
+
+    let mut result = [T::default(); N];
+    for (component, name) in result.iter_mut().zip(names) {
+        let chunk = iter.next().ok_or(CommonError::Parse(name))?;
+        *component = chunk.parse().map_err(|_| CommonError::Parse(name))?;
+    }
+
+    if iter.next().is_some() {
+        Err(CommonError::Parse(
+            "Too many components while parsing vector.",
+        ))
+    } else {
+        Ok(result)
+    }
+}
+
+impl<T: Scalar + FromStr> FromStr for Vec2<T> {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [x, y] = parse_components(
+            s,
+            [
+                "Missing or malformed x component while parsing vector.",
+                "Missing or malformed y component while parsing vector.",
+            ],
+        )?;
+        Ok(Vec2::new(x, y))
+    }
+}
+
+impl<T: Scalar + FromStr> FromStr for Vec3<T> {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let [x, y, z] = parse_components(
+            s,
+            [
+                "Missing or malformed x component while parsing vector.",
+                "Missing or malformed y component while parsing vector.",
+                "Missing or malformed z component while parsing vector.",
+            ],
+        )?;
+        Ok(Vec3::new(x, y, z))
+    }
+}
+
+/// Row-major 3x3 matrix.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Default)]
+pub struct Matrix3<T> {
+    pub rows: [[T; 3]; 3],
+}
+
+impl<T: Scalar> Matrix3<T> {
+    pub fn new(rows: [[T; 3]; 3]) -> Self {
+        Matrix3 { rows }
+    }
+
+    pub fn identity() -> Self {
+        let mut rows = [[T::default(); 3]; 3];
+        for (i, row) in rows.iter_mut().enumerate() {
+            row[i] = T::one();
+        }
+        Matrix3 { rows }
+    }
+
+    pub fn transpose(&self) -> Self {
+        let mut rows = [[T::default(); 3]; 3];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, e) in row.iter_mut().enumerate() {
+                *e = self.rows[j][i];
+            }
+        }
+        Matrix3 { rows }
+    }
+
+    pub fn determinant(&self) -> T {
+        let m = &self.rows;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+}
+
+impl<T: Scalar> Mul for Matrix3<T> {
+    type Output = Matrix3<T>;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut rows = [[T::default(); 3]; 3];
+        for (i, row) in rows.iter_mut().enumerate() {
+            for (j, e) in row.iter_mut().enumerate() {
+                for k in 0..3 {
+                    *e = *e + self.rows[i][k] * rhs.rows[k][j];
+                }
+            }
+        }
+        Matrix3 { rows }
+    }
+}
+
+impl<T: Scalar> Mul<Vec3<T>> for Matrix3<T> {
+    type Output = Vec3<T>;
+
+    fn mul(self, rhs: Vec3<T>) -> Self::Output {
+        let mut result = Vec3::default();
+        for i in 0..3 {
+            result[i] = Vec3::new(self.rows[i][0], self.rows[i][1], self.rows[i][2]).dot(&rhs);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vector_arithmetic() {
+        let a = Vec3::new(1i64, -2, 3);
+        let b = Vec3::new(4i64, 5, -6);
+
+        assert_eq!(a + b, Vec3::new(5, 3, -3));
+        assert_eq!(a - b, Vec3::new(-3, -7, 9));
+        assert_eq!(-a, Vec3::new(-1, 2, -3));
+        assert_eq!(a * 3, Vec3::new(3, -6, 9));
+        assert_eq!(Vec3::new(7i64, -7, 8) / 2, Vec3::new(3, -3, 4));
+        assert_eq!(a.dot(&b), 4 - 10 - 18);
+        assert_eq!(a.cross(&b), Vec3::new(-3, 18, 13));
+        assert_eq!(a.cross(&b).dot(&a), 0);
+        assert_eq!(a.component_min(&b), Vec3::new(1, -2, -6));
+        assert_eq!(a.component_max(&b), Vec3::new(4, 5, 3));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        c *= 2;
+        c /= 2;
+        assert_eq!(c, b);
+
+        let p = Vec2::new(3u64, 10);
+        let q = Vec2::new(5u64, 4);
+        assert_eq!(p.manhattan_distance(&q), 8);
+        assert_eq!(p.chebyshev_distance(&q), 6);
+        assert_eq!(Vec2::new(1i32, 0).cross(&Vec2::new(0, 1)), 1);
+    }
+
+    #[test]
+    fn test_vector_distances() {
+        let a = Vec3::new(1i64, -2, 3);
+        let b = Vec3::new(4i64, 5, -6);
+        assert_eq!(a.manhattan_distance(&b), 3 + 7 + 9);
+        assert_eq!(a.chebyshev_distance(&b), 9);
+        assert_eq!(a.manhattan_norm(), 6);
+        assert_eq!(a.squared_norm(), 14);
+    }
+
+    #[test]
+    fn test_vector_parse() {
+        assert_eq!("1,-2,3".parse::<Vec3<i64>>().unwrap(), Vec3::new(1, -2, 3));
+        assert_eq!("1 -2  3".parse::<Vec3<i64>>().unwrap(), Vec3::new(1, -2, 3));
+        assert_eq!(
+            "1, -2, 3\n".parse::<Vec3<i64>>().unwrap(),
+            Vec3::new(1, -2, 3)
+        );
+        assert_eq!("7,8".parse::<Vec2<usize>>().unwrap(), Vec2::new(7, 8));
+
+        let component_error = |s: &str| match s.parse::<Vec3<i64>>() {
+            Err(CommonError::Parse(msg)) => msg,
+            other => panic!("Expected parse error, got {:?}", other),
+        };
+        assert!(component_error("1,2").contains("z component"));
+        assert!(component_error("1,x,3").contains("y component"));
+        assert!(component_error("1,2,3,4").contains("Too many"));
+    }
+
+    #[test]
+    fn test_matrix() {
+        let rot_z = Matrix3::new([[0i64, -1, 0], [1, 0, 0], [0, 0, 1]]);
+        let v = Vec3::new(1i64, 2, 3);
+
+        assert_eq!(rot_z * v, Vec3::new(-2, 1, 3));
+        assert_eq!(rot_z * rot_z.transpose(), Matrix3::identity());
+        assert_eq!(rot_z.determinant(), 1);
+        assert_eq!(
+            Matrix3::new([[2i64, 0, 0], [0, 3, 0], [0, 0, 4]]).determinant(),
+            24
+        );
+        assert_eq!((rot_z * rot_z) * v, rot_z * (rot_z * v));
+    }
+}
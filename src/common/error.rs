@@ -7,6 +7,10 @@ pub enum CommonError {
     IntParse(num::ParseIntError),
     Parse(&'static str),
     Dimensions(String),
+    Evaluation(String),
+    Overflow(String),
+    Disconnected(Vec<usize>),
+    StepBudget(usize),
 }
 
 impl From<io::Error> for CommonError {
@@ -1,4 +1,4 @@
-use std::fs::{read_to_string, File};
+use std::fs::{self, read_to_string, File};
 use std::io::{self, BufRead, BufReader, Lines};
 use std::path::Path;
 use std::str::FromStr;
@@ -61,3 +61,11 @@ where
     let lines = get_lines_iterator(filename)?;
     parse::transform_lines(lines, |s| O::from_str(s))
 }
+
+pub fn write_string<T: AsRef<Path>, S: AsRef<str>>(
+    filename: T,
+    content: S,
+) -> Result<(), CommonError> {
+    fs::write(filename, content.as_ref())?;
+    Ok(())
+}
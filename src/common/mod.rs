@@ -1,5 +1,8 @@
 pub mod algorithms;
+pub mod big_uint;
+pub mod checked;
 pub mod collections;
 pub mod error;
 pub mod file_io;
 pub mod parse;
+pub mod rng;
@@ -0,0 +1,77 @@
+/// Small xorshift64* generator for reproducible randomized tests and simulations. Not suitable for
+/// anything that needs real randomness.
+#[derive(Debug, Clone)]
+pub struct XorShift64 {
+    state: u64,
+}
+
+impl XorShift64 {
+    /// Zero is a fixed point of xorshift, so it is swapped for another seed.
+    pub fn new(seed: u64) -> Self {
+        XorShift64 {
+            state: if seed == 0 {
+                0x9E37_79B9_7F4A_7C15
+            } else {
+                seed
+            },
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniform in `0..bound`, up to a bias of `bound / 2^64`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        assert!(bound > 0, "Empty range");
+        ((self.next_u64() as u128 * bound as u128) >> 64) as u64
+    }
+
+    /// Uniform in `start..=end`.
+    pub fn range_inclusive(&mut self, start: u64, end: u64) -> u64 {
+        assert!(start <= end, "Empty range");
+        match (end - start).checked_add(1) {
+            Some(len) => start + self.below(len),
+            None => self.next_u64(),
+        }
+    }
+
+    /// Fisher-Yates shuffle.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xorshift() {
+        let mut a = XorShift64::new(42);
+        let mut b = XorShift64::new(42);
+        let draws: Vec<u64> = (0..100).map(|_| a.range_inclusive(3, 7)).collect();
+        assert!(draws.iter().all(|&x| (3..=7).contains(&x)));
+        assert!((3..=7).all(|x| draws.contains(&x)));
+        assert_eq!(
+            draws,
+            (0..100)
+                .map(|_| b.range_inclusive(3, 7))
+                .collect::<Vec<_>>()
+        );
+
+        let mut zero = XorShift64::new(0);
+        assert_ne!(zero.next_u64(), zero.next_u64());
+
+        let mut items: Vec<u32> = (0..20).collect();
+        a.shuffle(&mut items);
+        items.sort_unstable();
+        assert_eq!(items, (0..20).collect::<Vec<_>>());
+    }
+}
@@ -1,78 +1,187 @@
 #![allow(dead_code)]
-fn find_most_common_bit_in_column<S: AsRef<str>>(lines: &[S], column: usize) -> char {
-    let sum = lines.iter().fold(0.0, |acc, line| {
-        let ch = line.as_ref().chars().nth(column).unwrap();
-        acc + ch.to_digit(10).unwrap() as f64
-    });
+use crate::common::big_uint::BigUint;
+use crate::common::error::CommonError;
+use std::str::FromStr;
 
-    if (sum / lines.len() as f64).round() == 0.0 {
-        '0'
-    } else {
-        '1'
-    }
+const WORD_BITS: usize = 64;
+
+fn popcount<I: IntoIterator<Item = u64>>(words: I) -> usize {
+    words.into_iter().map(|w| w.count_ones() as usize).sum()
 }
 
-fn inverse_char(ch: char) -> char {
-    if ch == '0' {
-        '1'
-    } else {
-        '0'
-    }
+/// Which bit value a rating keeps in each column.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Criterion {
+    MostCommon,
+    LeastCommon,
 }
 
-fn calculate_gamma_epsilon_mul<S: AsRef<str>>(lines: &[S]) -> u64 {
-    let line_length = lines[0].as_ref().len();
-    let most_common = (0..line_length).fold(String::new(), |mut bitvec, column| {
-        bitvec.push(find_most_common_bit_in_column(lines, column));
-        bitvec
-    });
+/// A criterion and the bit kept when both values are equally common.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct BitCriteria {
+    criterion: Criterion,
+    on_tie: bool,
+}
+
+const OXYGEN: BitCriteria = BitCriteria {
+    criterion: Criterion::MostCommon,
+    on_tie: true,
+};
+
+const CO2_SCRUBBER: BitCriteria = BitCriteria {
+    criterion: Criterion::LeastCommon,
+    on_tie: false,
+};
 
-    let gamma = u64::from_str_radix(&most_common, 2).unwrap();
-    let inverse = most_common.chars().map(inverse_char).collect::<String>();
-    let epsilon = u64::from_str_radix(&inverse, 2).unwrap();
+impl BitCriteria {
+    /// Bit to keep given how many of `total` values are ones. A value that does not occur at all
+    /// is never picked, so a column where all values agree keeps every one of them.
+    fn select(&self, ones: usize, total: usize) -> bool {
+        let zeros = total - ones;
+        if ones == 0 || zeros == 0 {
+            return ones > 0;
+        }
+        match (self.criterion, ones.cmp(&zeros)) {
+            (_, std::cmp::Ordering::Equal) => self.on_tie,
+            (Criterion::MostCommon, order) => order.is_gt(),
+            (Criterion::LeastCommon, order) => order.is_lt(),
+        }
+    }
+}
 
-    gamma * epsilon
+/// Diagnostic report stored column by column, each column a bit set over the rows, so counting the
+/// ones in a column among any subset of rows is a popcount of the masked words.
+#[derive(Debug, Clone)]
+struct Report {
+    width: usize,
+    num_rows: usize,
+    columns: Vec<Vec<u64>>,
+    // Ones per column over all rows
+    ones: Vec<usize>,
 }
 
-fn sieve_lines<S: AsRef<str>>(lines: &[S], most_common: bool) -> Vec<&S> {
-    let mut remaining: Vec<&S> = lines.iter().collect();
-    let line_length = lines[0].as_ref().len();
+impl Report {
+    fn from_lines<S: AsRef<str>>(lines: &[S]) -> Result<Self, CommonError> {
+        let width = lines
+            .first()
+            .ok_or(CommonError::Parse("Empty diagnostic report"))?
+            .as_ref()
+            .len();
+        let num_words = lines.len().div_ceil(WORD_BITS);
+        let mut columns = vec![vec![0u64; num_words]; width];
 
-    for column in 0..line_length {
-        let mut most_common_col = find_most_common_bit_in_column(&remaining, column);
-        if !most_common {
-            most_common_col = inverse_char(most_common_col)
+        for (row, line) in lines.iter().enumerate() {
+            let line = line.as_ref();
+            if line.len() != width {
+                return Err(CommonError::Parse("Report lines differ in width"));
+            }
+            for (column, ch) in line.chars().enumerate() {
+                match ch {
+                    '0' => {}
+                    '1' => columns[column][row / WORD_BITS] |= 1 << (row % WORD_BITS),
+                    _ => return Err(CommonError::Parse("Report lines must be binary")),
+                }
+            }
         }
 
-        remaining = remaining
-            .into_iter()
-            .filter(|line| line.as_ref().chars().nth(column).unwrap() == most_common_col)
+        let ones = columns
+            .iter()
+            .map(|words| popcount(words.iter().copied()))
+            .collect();
+        Ok(Report {
+            width,
+            num_rows: lines.len(),
+            columns,
+            ones,
+        })
+    }
+
+    fn ones_in_column(&self, column: usize) -> usize {
+        self.ones[column]
+    }
+
+    fn bit(&self, row: usize, column: usize) -> bool {
+        self.columns[column][row / WORD_BITS] >> (row % WORD_BITS) & 1 == 1
+    }
+
+    fn row_value(&self, row: usize) -> BigUint {
+        BigUint::from_bits((0..self.width).map(|column| self.bit(row, column)))
+    }
+
+    /// Gamma and epsilon rates: the most and least common bit of every column, with `on_tie` as
+    /// gamma's bit for evenly split columns.
+    fn power_rates(&self, on_tie: bool) -> (BigUint, BigUint) {
+        let criteria = BitCriteria {
+            criterion: Criterion::MostCommon,
+            on_tie,
+        };
+        let gamma_bits: Vec<bool> = (0..self.width)
+            .map(|column| criteria.select(self.ones_in_column(column), self.num_rows))
             .collect();
 
-        if remaining.len() == 1 {
-            break;
+        let gamma = BigUint::from_bits(gamma_bits.iter().copied());
+        let epsilon = BigUint::from_bits(gamma_bits.iter().map(|&bit| !bit));
+        (gamma, epsilon)
+    }
+
+    /// Keeps the rows matching the criteria column by column until one is left. Rows that are still
+    /// around after the last column are all equal.
+    fn rating(&self, criteria: BitCriteria) -> BigUint {
+        let mut mask = vec![u64::MAX; self.num_rows.div_ceil(WORD_BITS)];
+        if !self.num_rows.is_multiple_of(WORD_BITS) {
+            *mask.last_mut().unwrap() = (1 << (self.num_rows % WORD_BITS)) - 1;
+        }
+
+        for column in &self.columns {
+            let total = popcount(mask.iter().copied());
+            if total == 1 {
+                break;
+            }
+            let ones = popcount(mask.iter().zip(column).map(|(m, c)| m & c));
+            let keep_ones = criteria.select(ones, total);
+            for (m, &c) in mask.iter_mut().zip(column) {
+                *m &= if keep_ones { c } else { !c };
+            }
         }
+
+        let (word, bits) = mask.iter().enumerate().find(|(_, &w)| w != 0).unwrap();
+        self.row_value(word * WORD_BITS + bits.trailing_zeros() as usize)
     }
 
-    remaining
+    fn power_consumption(&self) -> BigUint {
+        let (gamma, epsilon) = self.power_rates(true);
+        gamma * epsilon
+    }
+
+    fn life_support_rating(&self) -> BigUint {
+        self.rating(OXYGEN) * self.rating(CO2_SCRUBBER)
+    }
 }
 
-fn find_oxygen_coscrubber_rating_mul<S: AsRef<str>>(lines: &[S]) -> u64 {
-    let oxygen_remaining = sieve_lines(lines, true);
-    assert_eq!(oxygen_remaining.len(), 1);
-    let oxygen = u64::from_str_radix(oxygen_remaining[0].as_ref(), 2).unwrap();
+impl FromStr for Report {
+    type Err = CommonError;
 
-    let co_scrubber_remaining = sieve_lines(lines, false);
-    assert_eq!(co_scrubber_remaining.len(), 1);
-    let co_scrubber = u64::from_str_radix(co_scrubber_remaining[0].as_ref(), 2).unwrap();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.lines().collect();
+        Report::from_lines(&lines)
+    }
+}
 
-    oxygen * co_scrubber
+fn calculate_gamma_epsilon_mul<S: AsRef<str>>(lines: &[S]) -> u64 {
+    let report = Report::from_lines(lines).unwrap();
+    report.power_consumption().to_u64().unwrap()
+}
+
+fn find_oxygen_coscrubber_rating_mul<S: AsRef<str>>(lines: &[S]) -> u64 {
+    let report = Report::from_lines(lines).unwrap();
+    report.life_support_rating().to_u64().unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::common::file_io;
+    use crate::common::rng::XorShift64;
 
     const TEST_DATA: &str = r"00100
 11110
@@ -101,6 +210,102 @@ mod tests {
         assert_eq!(result, 230)
     }
 
+    #[test]
+    fn test_report() {
+        let report: Report = TEST_DATA.parse().unwrap();
+        assert_eq!((report.width, report.num_rows), (5, 12));
+        assert_eq!(
+            (0..5).map(|c| report.ones_in_column(c)).collect::<Vec<_>>(),
+            vec![7, 5, 8, 7, 5]
+        );
+        let (gamma, epsilon) = report.power_rates(true);
+        assert_eq!((gamma.to_u64(), epsilon.to_u64()), (Some(22), Some(9)));
+        assert_eq!(report.rating(OXYGEN).to_u64(), Some(23));
+        assert_eq!(report.rating(CO2_SCRUBBER).to_u64(), Some(10));
+
+        assert!("".parse::<Report>().is_err());
+        assert!("01\n011".parse::<Report>().is_err());
+        assert!("01\n02".parse::<Report>().is_err());
+    }
+
+    #[test]
+    fn test_tie_breaking() {
+        let report: Report = "10\n01".parse().unwrap();
+        assert_eq!(report.rating(OXYGEN).to_u64(), Some(2));
+        assert_eq!(report.rating(CO2_SCRUBBER).to_u64(), Some(1));
+
+        let most_common_zero = BitCriteria {
+            criterion: Criterion::MostCommon,
+            on_tie: false,
+        };
+        assert_eq!(report.rating(most_common_zero).to_u64(), Some(1));
+        assert_eq!(report.power_rates(false).0.to_u64(), Some(0));
+
+        // Columns where every row agrees never eliminate everything
+        let report: Report = "110\n111\n111".parse().unwrap();
+        assert_eq!(report.rating(CO2_SCRUBBER).to_u64(), Some(6));
+    }
+
+    #[test]
+    fn test_wide_report() {
+        // 70 bits wide and over 64 rows, so both the values and the row masks span several words
+        let mut lines = vec![format!("1{}", "0".repeat(69)); 40];
+        lines.extend(vec!["0".repeat(70); 30]);
+        let report = Report::from_lines(&lines).unwrap();
+
+        assert_eq!(
+            report.power_consumption(),
+            "348449143727040986585905302199771942879232"
+                .parse()
+                .unwrap()
+        );
+        let (gamma, _) = report.power_rates(true);
+        assert_eq!(gamma.bits(), 70);
+        assert_eq!(report.rating(OXYGEN), gamma);
+        assert!(report.rating(CO2_SCRUBBER).is_zero());
+    }
+
+    // Straightforward version over the strings
+    fn naive_rating(lines: &[String], criteria: BitCriteria) -> u64 {
+        let mut remaining: Vec<&String> = lines.iter().collect();
+        for column in 0..lines[0].len() {
+            if remaining.len() == 1 {
+                break;
+            }
+            let is_one = |line: &&String| line.as_bytes()[column] == b'1';
+            let ones = remaining.iter().filter(|line| is_one(line)).count();
+            let keep_ones = criteria.select(ones, remaining.len());
+            remaining.retain(|line| is_one(line) == keep_ones);
+        }
+        u64::from_str_radix(remaining[0], 2).unwrap()
+    }
+
+    #[test]
+    fn test_rating_random() {
+        let mut rng = XorShift64::new(3);
+        for _ in 0..100 {
+            let width = rng.range_inclusive(1, 12) as usize;
+            let lines: Vec<String> = (0..rng.range_inclusive(1, 150))
+                .map(|_| {
+                    (0..width)
+                        .map(|_| if rng.below(2) == 1 { '1' } else { '0' })
+                        .collect()
+                })
+                .collect();
+            let report = Report::from_lines(&lines).unwrap();
+
+            for criterion in [Criterion::MostCommon, Criterion::LeastCommon] {
+                for on_tie in [false, true] {
+                    let criteria = BitCriteria { criterion, on_tie };
+                    assert_eq!(
+                        report.rating(criteria).to_u64(),
+                        Some(naive_rating(&lines, criteria))
+                    );
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_d03() {
         let lines = file_io::read_lines_as_strings("inputs/d03").unwrap();
@@ -1,64 +1,112 @@
 #![allow(dead_code)]
-// We could probably do a time/memory tradeoff here by making a different data structure,
-// but not sure if it would be faster in real-life.
+use crate::common::rng::XorShift64;
+use std::collections::HashMap;
+
+/// Ways to complete a board on top of each other. Diagonals only exist on square boards.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct WinPatterns {
+    rows: bool,
+    columns: bool,
+    diagonals: bool,
+    corners: bool,
+    full_card: bool,
+}
+
+/// Rows and columns, as in the puzzle.
+impl Default for WinPatterns {
+    fn default() -> Self {
+        WinPatterns {
+            rows: true,
+            columns: true,
+            diagonals: false,
+            corners: false,
+            full_card: false,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 struct Board {
-    numbers: [[u64; 5]; 5],
-    marked: [[bool; 5]; 5],
+    rows: usize,
+    cols: usize,
+    // Row-major
+    numbers: Vec<u64>,
+    marked: Vec<bool>,
 }
 
 impl<S: AsRef<str>> From<&[S]> for Board {
     fn from(chunk: &[S]) -> Self {
-        assert_eq!(chunk.len(), 5);
-
-        let mut numbers = [[0u64; 5]; 5];
-        let marked = [[false; 5]; 5];
+        let rows: Vec<Vec<u64>> = chunk
+            .iter()
+            .map(|row| {
+                row.as_ref()
+                    .split_whitespace()
+                    .map(|s| s.parse().unwrap())
+                    .collect()
+            })
+            .collect();
 
-        for row in 0..5 {
-            let splitted: Vec<(usize, u64)> = chunk[row]
-                .as_ref()
-                .split_whitespace()
-                .map(|s| s.parse().unwrap())
-                .enumerate()
-                .collect();
+        let cols = rows[0].len();
+        assert!(rows.iter().all(|row| row.len() == cols), "Ragged board");
+        Board::new(rows.len(), cols, rows.concat())
+    }
+}
 
-            assert_eq!(splitted.len(), 5);
-            splitted.iter().for_each(|(col, x)| numbers[row][*col] = *x);
+impl Board {
+    fn new(rows: usize, cols: usize, numbers: Vec<u64>) -> Self {
+        assert_eq!(numbers.len(), rows * cols);
+        Board {
+            rows,
+            cols,
+            numbers,
+            marked: vec![false; rows * cols],
         }
+    }
 
-        Board { numbers, marked }
+    fn is_marked(&self, row: usize, col: usize) -> bool {
+        self.marked[row * self.cols + col]
+    }
+
+    fn mark(&mut self, row: usize, col: usize) {
+        self.marked[row * self.cols + col] = true;
     }
-}
 
-impl Board {
     fn check_victory(&self) -> bool {
-        for i in 0..5 {
-            let mut victory = true;
-            for j in 0..5 {
-                if !self.marked[i][j] {
-                    victory = false;
-                    break;
-                }
-            }
-            if victory {
-                return true;
-            }
-        }
+        self.check_pattern_victory(&WinPatterns::default())
+    }
 
-        for i in 0..5 {
-            let mut victory = true;
-            for j in 0..5 {
-                if !self.marked[j][i] {
-                    victory = false;
-                    break;
-                }
-            }
-            if victory {
-                return true;
-            }
-        }
+    fn all_marked<I: IntoIterator<Item = (usize, usize)>>(&self, cells: I) -> bool {
+        cells.into_iter().all(|(row, col)| self.is_marked(row, col))
+    }
 
-        false
+    fn check_pattern_victory(&self, patterns: &WinPatterns) -> bool {
+        let (rows, cols) = (self.rows, self.cols);
+
+        let row_win = || (0..rows).any(|row| self.all_marked((0..cols).map(|col| (row, col))));
+        let col_win = || (0..cols).any(|col| self.all_marked((0..rows).map(|row| (row, col))));
+        let diagonal_win = || {
+            rows == cols
+                && (self.all_marked((0..rows).map(|i| (i, i)))
+                    || self.all_marked((0..rows).map(|i| (i, cols - 1 - i))))
+        };
+        let corner_win =
+            || self.all_marked([(0, 0), (0, cols - 1), (rows - 1, 0), (rows - 1, cols - 1)]);
+        let full_card_win = || self.marked.iter().all(|&marked| marked);
+
+        (patterns.rows && row_win())
+            || (patterns.columns && col_win())
+            || (patterns.diagonals && diagonal_win())
+            || (patterns.corners && corner_win())
+            || (patterns.full_card && full_card_win())
+    }
+
+    fn unmarked_sum(&self) -> u64 {
+        self.numbers
+            .iter()
+            .zip(&self.marked)
+            .filter(|(_, &marked)| !marked)
+            .map(|(number, _)| number)
+            .sum()
     }
 }
 
@@ -78,64 +126,166 @@ fn parse_input<S: AsRef<str>>(chunks: &[Vec<S>]) -> (Vec<u64>, Vec<Board>) {
     (draws, boards)
 }
 
-fn execute_draw(draw: u64, boards: &mut [Board]) {
-    for mut board in boards {
-        for row in 0..5 {
-            for col in 0..5 {
-                if board.numbers[row][col] == draw {
-                    board.marked[row][col] = true;
-                }
+/// A board completing one of the win patterns.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct WinEvent {
+    board: usize,
+    // Position in the draw order
+    draw_index: usize,
+    number: u64,
+    score: u64,
+}
+
+/// Bingo hall that marks every copy of a drawn number through an index instead of scanning the
+/// boards. Boards stop being marked once they have won, so they keep their winning state.
+struct Bingo {
+    boards: Vec<Board>,
+    patterns: WinPatterns,
+    // Number to every (board, row, column) holding it
+    index: HashMap<u64, Vec<(usize, usize, usize)>>,
+    won: Vec<bool>,
+    num_drawn: usize,
+}
+
+impl Bingo {
+    fn new(boards: &[Board], patterns: WinPatterns) -> Self {
+        let mut index: HashMap<u64, Vec<(usize, usize, usize)>> = HashMap::new();
+        for (board_num, board) in boards.iter().enumerate() {
+            for (cell, &number) in board.numbers.iter().enumerate() {
+                let position = (board_num, cell / board.cols, cell % board.cols);
+                index.entry(number).or_default().push(position);
             }
         }
+
+        Bingo {
+            boards: boards.to_vec(),
+            patterns,
+            index,
+            won: vec![false; boards.len()],
+            num_drawn: 0,
+        }
     }
-}
 
-fn play(draws: &[u64], orig_boards: &[Board]) -> Option<(u64, Board)> {
-    let mut boards = orig_boards.to_vec();
+    /// Marks the number and returns the boards that won with it, in board order.
+    fn draw(&mut self, number: u64) -> Vec<WinEvent> {
+        let draw_index = self.num_drawn;
+        self.num_drawn += 1;
+
+        let mut touched = Vec::new();
+        for &(board, row, col) in self.index.get(&number).into_iter().flatten() {
+            if !self.won[board] {
+                self.boards[board].mark(row, col);
+                touched.push(board);
+            }
+        }
+        touched.dedup(); // The index is grouped by board
+
+        touched
+            .into_iter()
+            .filter(|&board| self.boards[board].check_pattern_victory(&self.patterns))
+            .map(|board| {
+                self.won[board] = true;
+                WinEvent {
+                    board,
+                    draw_index,
+                    number,
+                    score: calculate_mul(number, &self.boards[board]),
+                }
+            })
+            .collect()
+    }
 
-    for draw in draws {
-        execute_draw(*draw, &mut boards);
-        if let Some(winner) = boards.iter().find(|board| board.check_victory()) {
-            return Some((*draw, winner.clone()));
+    /// Clears every mark and win so another game can be played on the same boards and index.
+    fn reset(&mut self) {
+        for board in &mut self.boards {
+            board.marked.fill(false);
         }
+        self.won.fill(false);
+        self.num_drawn = 0;
     }
 
-    None
+    /// Every win over the whole draw order, in the order they happen.
+    fn play_all(&mut self, draws: &[u64]) -> Vec<WinEvent> {
+        draws.iter().flat_map(|&draw| self.draw(draw)).collect()
+    }
+}
+
+fn play(draws: &[u64], orig_boards: &[Board]) -> Option<(u64, Board)> {
+    let mut bingo = Bingo::new(orig_boards, WinPatterns::default());
+    draws
+        .iter()
+        .find_map(|&draw| bingo.draw(draw).first().copied())
+        .map(|event| (event.number, bingo.boards[event.board].clone()))
 }
 
 fn play_until_last(draws: &[u64], orig_boards: &[Board]) -> Option<(u64, Board)> {
-    let mut boards = orig_boards.to_vec();
-    let mut winning_tuple = None;
+    let mut bingo = Bingo::new(orig_boards, WinPatterns::default());
+    let events = bingo.play_all(draws);
+    events
+        .last()
+        .map(|event| (event.number, bingo.boards[event.board].clone()))
+}
 
-    for draw in draws {
-        execute_draw(*draw, &mut boards);
-        let winners: Vec<usize> = boards
-            .iter()
-            .enumerate()
-            .filter(|(_, board)| board.check_victory())
-            .map(|(pos, _)| pos)
-            .rev()
-            .collect();
+/// Outcome of one board over many shuffled draw orders.
+#[derive(Debug, PartialEq, Clone, Copy)]
+struct BoardStats {
+    /// Share of games the board won. Boards winning on the same draw share that game equally.
+    win_probability: f64,
+    /// Mean draw index at which the board wins, over the games it won or tied for, or None if it
+    /// never won.
+    expected_winning_draw: Option<f64>,
+}
 
-        for winner_pos in winners {
-            winning_tuple = Some((*draw, boards[winner_pos].clone()));
-            boards.remove(winner_pos);
+/// Plays `num_games` games on shuffles of `draws`, from a seeded generator so runs can be
+/// reproduced. There are no statistics without any games.
+fn simulate_win_probabilities(
+    draws: &[u64],
+    boards: &[Board],
+    patterns: WinPatterns,
+    num_games: usize,
+    seed: u64,
+) -> Option<Vec<BoardStats>> {
+    if num_games == 0 {
+        return None;
+    }
+
+    let mut rng = XorShift64::new(seed);
+    let mut draws = draws.to_vec();
+    let mut bingo = Bingo::new(boards, patterns);
+    let mut wins = vec![0.0; boards.len()];
+    let mut draw_index_sums = vec![0; boards.len()];
+    let mut num_won = vec![0; boards.len()];
+
+    for _ in 0..num_games {
+        rng.shuffle(&mut draws);
+        bingo.reset();
+        let events = bingo.play_all(&draws);
+
+        if let Some(first) = events.first() {
+            let winners: Vec<&WinEvent> = events
+                .iter()
+                .take_while(|event| event.draw_index == first.draw_index)
+                .collect();
+            for winner in &winners {
+                wins[winner.board] += 1.0 / winners.len() as f64;
+                draw_index_sums[winner.board] += winner.draw_index;
+                num_won[winner.board] += 1;
+            }
         }
     }
 
-    winning_tuple
+    let stats = (0..boards.len())
+        .map(|board| BoardStats {
+            win_probability: wins[board] / num_games as f64,
+            expected_winning_draw: (num_won[board] > 0)
+                .then(|| draw_index_sums[board] as f64 / num_won[board] as f64),
+        })
+        .collect();
+    Some(stats)
 }
 
 fn calculate_mul(winning_draw: u64, winning_board: &Board) -> u64 {
-    let mut sum = 0;
-    for row in 0..5 {
-        for col in 0..5 {
-            if !winning_board.marked[row][col] {
-                sum += winning_board.numbers[row][col]
-            }
-        }
-    }
-    sum * winning_draw
+    winning_board.unmarked_sum() * winning_draw
 }
 
 #[cfg(test)]
@@ -165,26 +315,121 @@ mod tests {
 
     #[test]
     fn test_check_victory() {
-        let nums = [[0u64; 5]; 5];
-        let mut marked = [[false; 5]; 5];
-        marked[1][1] = true;
-        let board_loss = Board {
-            numbers: nums,
-            marked,
-        };
+        let mut board_loss = Board::new(5, 5, vec![0; 25]);
+        board_loss.mark(1, 1);
         assert!(!board_loss.check_victory());
 
         let mut board_row_victory = board_loss.clone();
-        board_row_victory.marked[2] = [true; 5];
+        (0..5).for_each(|col| board_row_victory.mark(2, col));
         assert!(board_row_victory.check_victory());
 
         let mut board_col_victory = board_loss;
         for row in 0..5 {
-            board_col_victory.marked[row][2] = true;
+            board_col_victory.mark(row, 2);
         }
         assert!(board_col_victory.check_victory());
     }
 
+    #[test]
+    fn test_check_pattern_victory() {
+        let all = WinPatterns {
+            rows: true,
+            columns: true,
+            diagonals: true,
+            corners: true,
+            full_card: true,
+        };
+        let only = |f: fn(&mut WinPatterns)| {
+            let mut patterns = WinPatterns {
+                rows: false,
+                columns: false,
+                diagonals: false,
+                corners: false,
+                full_card: false,
+            };
+            f(&mut patterns);
+            patterns
+        };
+
+        let mut square = Board::new(3, 3, (0..9).collect());
+        [(0, 2), (1, 1), (2, 0)]
+            .iter()
+            .for_each(|&(r, c)| square.mark(r, c));
+        assert!(!square.check_victory());
+        assert!(square.check_pattern_victory(&only(|p| p.diagonals = true)));
+
+        // Diagonals do not count on other shapes
+        let rows: Vec<String> = vec!["1 2 3 4".into(), "5 6 7 8".into(), "9 10 11 12".into()];
+        let mut wide = Board::from(rows.as_slice());
+        assert_eq!((wide.rows, wide.cols), (3, 4));
+        [(0, 0), (1, 1), (2, 2)]
+            .iter()
+            .for_each(|&(r, c)| wide.mark(r, c));
+        assert!(!wide.check_pattern_victory(&all));
+
+        [(0, 3), (2, 0), (2, 3)]
+            .iter()
+            .for_each(|&(r, c)| wide.mark(r, c));
+        assert!(wide.check_pattern_victory(&only(|p| p.corners = true)));
+        assert!(!wide.check_pattern_victory(&only(|p| p.full_card = true)));
+
+        (0..12).for_each(|cell| wide.mark(cell / 4, cell % 4));
+        assert!(wide.check_pattern_victory(&only(|p| p.full_card = true)));
+        assert_eq!(wide.unmarked_sum(), 0);
+    }
+
+    #[test]
+    fn test_bingo() {
+        let chunks = parse::split_per_double_newline(TEST_DATA);
+        let (draws, boards) = parse_input(&chunks);
+
+        let mut bingo = Bingo::new(&boards, WinPatterns::default());
+        assert_eq!(bingo.index[&24], vec![(0, 1, 4), (1, 3, 3), (2, 0, 3)]);
+        let events = bingo.play_all(&draws);
+        let summary: Vec<(usize, usize, u64)> = events
+            .iter()
+            .map(|event| (event.board, event.draw_index, event.score))
+            .collect();
+        assert_eq!(summary, vec![(2, 11, 4512), (0, 13, 2192), (1, 14, 1924)]);
+
+        // A reset hall replays the same game
+        bingo.reset();
+        assert_eq!(bingo.play_all(&draws), events);
+
+        // Numbers repeated within a board are all marked at once
+        let mut bingo = Bingo::new(
+            &[Board::new(2, 2, vec![1, 1, 2, 3])],
+            WinPatterns::default(),
+        );
+        let events = bingo.draw(1);
+        assert_eq!(
+            events,
+            vec![WinEvent {
+                board: 0,
+                draw_index: 0,
+                number: 1,
+                score: 5
+            }]
+        );
+        // Won boards are left alone
+        assert!(bingo.draw(2).is_empty());
+        assert_eq!(bingo.boards[0].unmarked_sum(), 5);
+
+        let mut blackout = Bingo::new(
+            &boards,
+            WinPatterns {
+                rows: false,
+                columns: false,
+                diagonals: false,
+                corners: false,
+                full_card: true,
+            },
+        );
+        let events = blackout.play_all(&draws);
+        assert_eq!(events.len(), 3);
+        assert!(events.iter().all(|event| event.score == 0));
+    }
+
     #[test]
     fn test_play() {
         let chunks = parse::split_per_double_newline(&TEST_DATA);
@@ -207,6 +452,60 @@ mod tests {
         };
     }
 
+    #[test]
+    fn test_simulate_win_probabilities() {
+        let chunks = parse::split_per_double_newline(TEST_DATA);
+        let (draws, boards) = parse_input(&chunks);
+
+        let stats =
+            simulate_win_probabilities(&draws, &boards, WinPatterns::default(), 500, 4).unwrap();
+        assert_eq!(
+            stats,
+            simulate_win_probabilities(&draws, &boards, WinPatterns::default(), 500, 4).unwrap()
+        );
+        // Every number is drawn eventually, so every game has a winner
+        let total: f64 = stats.iter().map(|s| s.win_probability).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+        assert!(stats.iter().all(|s| match s.expected_winning_draw {
+            Some(d) => s.win_probability > 0.0 && (4.0..27.0).contains(&d),
+            None => s.win_probability == 0.0,
+        }));
+
+        // A single cell against a row containing it: the row can at best tie, when 6 comes
+        // first, which makes the single cell win 3/4 of the games
+        let boards = [Board::new(1, 1, vec![5]), Board::new(1, 2, vec![5, 6])];
+        let rows_only = WinPatterns {
+            columns: false,
+            ..WinPatterns::default()
+        };
+        let stats = simulate_win_probabilities(&[5, 6], &boards, rows_only, 10000, 7).unwrap();
+        assert!((stats[0].win_probability - 0.75).abs() < 0.02);
+        assert!((stats[1].win_probability - 0.25).abs() < 0.02);
+        assert!((stats[0].expected_winning_draw.unwrap() - 0.5).abs() < 0.02);
+        assert_eq!(stats[1].expected_winning_draw, Some(1.0));
+
+        // Numbers that are never drawn
+        let stats = simulate_win_probabilities(&[1], &boards, rows_only, 10, 7).unwrap();
+        assert!(stats
+            .iter()
+            .all(|s| s.win_probability == 0.0 && s.expected_winning_draw.is_none()));
+
+        // The long row always completes, but only after one of the single cells has already won
+        let boards = [
+            Board::new(1, 1, vec![5]),
+            Board::new(1, 1, vec![6]),
+            Board::new(1, 3, vec![5, 6, 7]),
+        ];
+        let stats = simulate_win_probabilities(&[5, 6, 7], &boards, rows_only, 1000, 7).unwrap();
+        assert_eq!(stats[2].win_probability, 0.0);
+        assert_eq!(stats[2].expected_winning_draw, None);
+        assert!(stats[..2].iter().all(|s| s
+            .expected_winning_draw
+            .is_some_and(|d| (0.0..=1.0).contains(&d))));
+
+        assert!(simulate_win_probabilities(&[5, 6], &boards, rows_only, 0, 7).is_none());
+    }
+
     #[test]
     fn test_d04() {
         let chunks = file_io::read_lines_as_string_groups("inputs/d04").unwrap();
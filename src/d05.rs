@@ -1,6 +1,7 @@
 #![allow(dead_code)]
 use crate::common::collections::sparse_grid::{HashGrid, SparseGrid};
 use crate::common::error::CommonError;
+use std::collections::{BTreeSet, HashMap};
 use std::str::FromStr;
 
 #[derive(Debug)]
@@ -39,30 +40,55 @@ impl FromStr for Line {
     }
 }
 
-fn trace_line_on_grid(line: &Line, grid: &mut HashGrid<usize>) {
-    let x_diff = line.x1 as f64 - line.x2 as f64;
-    let y_diff = line.y1 as f64 - line.y2 as f64;
+/// Which cells a line puts on the grid.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Raster {
+    /// Only the points with integer coordinates that lie exactly on the line.
+    LatticePoints,
+    /// One cell per step along the longer axis, the one nearest to the line on the other axis,
+    /// with halves rounded up. A line and its reverse cover the same cells.
+    CoveredCells,
+}
 
-    if x_diff.abs() >= y_diff.abs() {
-        for x in 0..=x_diff.abs() as usize {
-            // Linear interpolation
-            let y = ((y_diff * x as f64) / x_diff.abs()).round();
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
 
-            let x_pos = (line.x1 as f64 - x as f64 * x_diff.signum()) as usize;
-            let y_pos = (line.y1 as f64 - y) as usize;
+// Nearest integer to num / den for den > 0, halves rounded towards positive infinity
+fn div_round_half_up(num: i64, den: i64) -> i64 {
+    (2 * num + den).div_euclid(2 * den)
+}
 
-            *grid.get_or_insert_pos_mut(x_pos, y_pos, &0) += 1;
+impl Line {
+    fn points(&self, raster: Raster) -> Vec<(usize, usize)> {
+        let dx = self.x2 as i64 - self.x1 as i64;
+        let dy = self.y2 as i64 - self.y1 as i64;
+
+        let num_steps = match raster {
+            Raster::LatticePoints => gcd(dx, dy),
+            Raster::CoveredCells => dx.abs().max(dy.abs()),
+        };
+        if num_steps == 0 {
+            return vec![(self.x1, self.y1)];
         }
-    } else {
-        for y in 0..=y_diff.abs() as usize {
-            // Linear interpolation
-            let x = ((x_diff * y as f64) / y_diff.abs()).round();
 
-            let x_pos = (line.x1 as f64 - x) as usize;
-            let y_pos = (line.y1 as f64 - y as f64 * y_diff.signum()) as usize;
+        (0..=num_steps)
+            .map(|i| {
+                let x = self.x1 as i64 + div_round_half_up(dx * i, num_steps);
+                let y = self.y1 as i64 + div_round_half_up(dy * i, num_steps);
+                (x as usize, y as usize)
+            })
+            .collect()
+    }
+}
 
-            *grid.get_or_insert_pos_mut(x_pos, y_pos, &0) += 1;
-        }
+fn trace_line_on_grid(line: &Line, raster: Raster, grid: &mut HashGrid<usize>) {
+    for (x, y) in line.points(raster) {
+        *grid.get_or_insert_pos_mut(x, y, &0) += 1;
     }
 }
 
@@ -75,7 +101,7 @@ fn find_non_diag_overlaps(lines: &[Line]) -> usize {
     lines
         .iter()
         .filter(|line| line.x1 == line.x2 || line.y1 == line.y2)
-        .for_each(|line| trace_line_on_grid(line, &mut grid));
+        .for_each(|line| trace_line_on_grid(line, Raster::LatticePoints, &mut grid));
 
     count_overlaps(&grid)
 }
@@ -84,14 +110,206 @@ fn find_all_overlaps(lines: &[Line]) -> usize {
     let mut grid = HashGrid::new();
     lines
         .iter()
-        .for_each(|line| trace_line_on_grid(line, &mut grid));
+        .for_each(|line| trace_line_on_grid(line, Raster::LatticePoints, &mut grid));
 
     count_overlaps(&grid)
 }
 
+// Infinite line through a segment: its primitive direction (a, b) with a > 0, or a == 0 and b > 0,
+// and the offset b * x - a * y shared by all of its points. Single points count as horizontal.
+type LineKey = (i64, i64, i64);
+
+fn line_key(line: &Line) -> LineKey {
+    let dx = line.x2 as i64 - line.x1 as i64;
+    let dy = line.y2 as i64 - line.y1 as i64;
+    let (a, b) = match gcd(dx, dy) {
+        0 => (1, 0),
+        g if dx < 0 || (dx == 0 && dy < 0) => (-dx / g, -dy / g),
+        g => (dx / g, dy / g),
+    };
+    (a, b, b * line.x1 as i64 - a * line.y1 as i64)
+}
+
+// All segments on the same infinite line, as ranges of the lattice points on it. Point number t is
+// origin + t * (a, b).
+struct CollinearSegments {
+    origin: (i64, i64),
+    direction: (i64, i64),
+    ranges: Vec<(i64, i64)>,
+    // Maximal ranges of constant nonzero coverage, in order
+    runs: Vec<(i64, i64, usize)>,
+}
+
+impl CollinearSegments {
+    fn index_of(&self, (x, y): (i64, i64)) -> i64 {
+        let (a, b) = self.direction;
+        if a != 0 {
+            (x - self.origin.0) / a
+        } else {
+            (y - self.origin.1) / b
+        }
+    }
+
+    fn point_at(&self, t: i64) -> (i64, i64) {
+        let (a, b) = self.direction;
+        (self.origin.0 + t * a, self.origin.1 + t * b)
+    }
+
+    // One dimensional sweep over the range ends
+    fn compute_runs(&mut self) {
+        let mut events: Vec<(i64, i64)> = self
+            .ranges
+            .iter()
+            .flat_map(|&(start, end)| [(start, 1), (end + 1, -1)])
+            .collect();
+        events.sort_unstable();
+
+        let mut coverage = 0;
+        for (i, &(t, change)) in events.iter().enumerate() {
+            coverage += change;
+            match events.get(i + 1) {
+                Some(&(next, _)) if next > t && coverage > 0 => {
+                    self.runs.push((t, next - 1, coverage as usize))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn coverage_at(&self, t: i64) -> usize {
+        let i = self.runs.partition_point(|&(_, end, _)| end < t);
+        match self.runs.get(i) {
+            Some(&(start, _, coverage)) if start <= t => coverage,
+            _ => 0,
+        }
+    }
+
+    fn count_covered(&self, min_lines: usize) -> usize {
+        self.runs
+            .iter()
+            .filter(|&&(_, _, coverage)| coverage >= min_lines)
+            .map(|&(start, end, _)| (end - start + 1) as usize)
+            .sum()
+    }
+}
+
+struct Segment {
+    key: LineKey,
+    range: (i64, i64),
+    x_range: (i64, i64),
+    y_range: (i64, i64),
+}
+
+// Lattice point where two segments on different lines cross, if any
+fn find_crossing(
+    first: &Segment,
+    second: &Segment,
+    groups: &HashMap<LineKey, CollinearSegments>,
+) -> Option<(i64, i64)> {
+    let (group1, group2) = (&groups[&first.key], &groups[&second.key]);
+    let (a1, b1) = group1.direction;
+    let (a2, b2) = group2.direction;
+    let denominator = a1 * b2 - b1 * a2;
+    if denominator == 0 {
+        return None; // Parallel
+    }
+
+    // Solve origin1 + t * d1 = origin2 + u * d2 for t
+    let ox = group2.origin.0 - group1.origin.0;
+    let oy = group2.origin.1 - group1.origin.1;
+    let numerator = ox * b2 - oy * a2;
+    if numerator % denominator != 0 {
+        return None; // Between lattice points
+    }
+    let t = numerator / denominator;
+    let point = group1.point_at(t);
+
+    let u = group2.index_of(point);
+    let within = |t: i64, (start, end): (i64, i64)| start <= t && t <= end;
+    (within(t, first.range) && group2.point_at(u) == point && within(u, second.range))
+        .then_some(point)
+}
+
+/// Number of lattice points covered by at least `min_lines` lines, without rasterizing. Collinear
+/// segments are merged by a sweep along their common line, and the crossings of different lines
+/// are found by sweeping across x while keeping the segments whose x range is still open.
+fn find_overlaps_analytic(lines: &[Line], min_lines: usize) -> usize {
+    assert!(
+        min_lines > 0,
+        "Every point is covered by at least zero lines"
+    );
+
+    let mut groups: HashMap<LineKey, CollinearSegments> = HashMap::new();
+    let mut segments = Vec::with_capacity(lines.len());
+    for line in lines {
+        let key = line_key(line);
+        let start = (line.x1 as i64, line.y1 as i64);
+        let end = (line.x2 as i64, line.y2 as i64);
+        let group = groups.entry(key).or_insert_with(|| CollinearSegments {
+            origin: start,
+            direction: (key.0, key.1),
+            ranges: Vec::new(),
+            runs: Vec::new(),
+        });
+
+        let (t1, t2) = (group.index_of(start), group.index_of(end));
+        let range = (t1.min(t2), t1.max(t2));
+        group.ranges.push(range);
+        segments.push(Segment {
+            key,
+            range,
+            x_range: (start.0.min(end.0), start.0.max(end.0)),
+            y_range: (start.1.min(end.1), start.1.max(end.1)),
+        });
+    }
+    groups.values_mut().for_each(|group| group.compute_runs());
+
+    segments.sort_unstable_by_key(|segment| segment.x_range);
+    let mut crossings: HashMap<(i64, i64), BTreeSet<LineKey>> = HashMap::new();
+    let mut active: Vec<&Segment> = Vec::new();
+    for segment in &segments {
+        active.retain(|other| other.x_range.1 >= segment.x_range.0);
+        for other in &active {
+            let y_overlap =
+                other.y_range.0 <= segment.y_range.1 && segment.y_range.0 <= other.y_range.1;
+            if !y_overlap || other.key == segment.key {
+                continue;
+            }
+            if let Some(point) = find_crossing(segment, other, &groups) {
+                let keys = crossings.entry(point).or_default();
+                keys.insert(segment.key);
+                keys.insert(other.key);
+            }
+        }
+        active.push(segment);
+    }
+
+    // Count along every line on its own, then fix up the points where lines cross
+    let mut count: usize = groups
+        .values()
+        .map(|group| group.count_covered(min_lines))
+        .sum();
+    for (point, keys) in crossings {
+        let coverages: Vec<usize> = keys
+            .iter()
+            .map(|key| {
+                let group = &groups[key];
+                group.coverage_at(group.index_of(point))
+            })
+            .collect();
+        count -= coverages.iter().filter(|&&c| c >= min_lines).count();
+        if coverages.iter().sum::<usize>() >= min_lines {
+            count += 1;
+        }
+    }
+
+    count
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::rng::XorShift64;
     use crate::common::{file_io, parse};
     const TEST_DATA: &str = r"0,9 -> 5,9
 8,0 -> 0,8
@@ -116,7 +334,7 @@ mod tests {
             y2: 0,
         };
 
-        trace_line_on_grid(&line_horizontal, &mut grid);
+        trace_line_on_grid(&line_horizontal, Raster::CoveredCells, &mut grid);
         assert_eq!(grid.get_pos(0, 0), Some(&1));
         assert_eq!(grid.get_pos(3, 0), Some(&1));
         assert_eq!(grid.get_pos(5, 0), Some(&1));
@@ -128,8 +346,8 @@ mod tests {
             x2: 0,
             y2: 0,
         };
-        trace_line_on_grid(&line_vertical, &mut grid);
-        trace_line_on_grid(&line_vertical, &mut grid);
+        trace_line_on_grid(&line_vertical, Raster::CoveredCells, &mut grid);
+        trace_line_on_grid(&line_vertical, Raster::CoveredCells, &mut grid);
         assert_eq!(grid.get_pos(0, 0), Some(&3));
         assert_eq!(grid.get_pos(0, 3), Some(&2));
         assert_eq!(grid.get_pos(0, 5), Some(&2));
@@ -142,7 +360,7 @@ mod tests {
             y2: 3,
         };
 
-        trace_line_on_grid(&line_diag, &mut grid);
+        trace_line_on_grid(&line_diag, Raster::CoveredCells, &mut grid);
         assert_eq!(grid.get_pos(3, 1), Some(&1));
         assert_eq!(grid.get_pos(2, 2), Some(&1));
         assert_eq!(grid.get_pos(1, 3), Some(&1));
@@ -154,12 +372,72 @@ mod tests {
             y2: 1,
         };
 
-        trace_line_on_grid(&line_diag_rev, &mut grid);
+        trace_line_on_grid(&line_diag_rev, Raster::CoveredCells, &mut grid);
         assert_eq!(grid.get_pos(1, 3), Some(&2));
         assert_eq!(grid.get_pos(2, 2), Some(&2));
         assert_eq!(grid.get_pos(3, 1), Some(&2));
     }
 
+    #[test]
+    fn test_points() {
+        let line: Line = "1,2 -> 7,6".parse().unwrap();
+        assert_eq!(
+            line.points(Raster::LatticePoints),
+            vec![(1, 2), (4, 4), (7, 6)]
+        );
+        assert_eq!(
+            line.points(Raster::CoveredCells),
+            vec![(1, 2), (2, 3), (3, 3), (4, 4), (5, 5), (6, 5), (7, 6)]
+        );
+
+        // Steep lines step along y, and nothing but the end points lies on a coprime slope
+        let line: Line = "3,0 -> 1,5".parse().unwrap();
+        assert_eq!(line.points(Raster::LatticePoints), vec![(3, 0), (1, 5)]);
+        assert_eq!(
+            line.points(Raster::CoveredCells),
+            vec![(3, 0), (3, 1), (2, 2), (2, 3), (1, 4), (1, 5)]
+        );
+
+        let point: Line = "4,4 -> 4,4".parse().unwrap();
+        assert_eq!(point.points(Raster::LatticePoints), vec![(4, 4)]);
+        assert_eq!(point.points(Raster::CoveredCells), vec![(4, 4)]);
+    }
+
+    #[test]
+    fn test_points_properties() {
+        for (x1, y1, x2, y2) in (0..81).map(|i| (i % 3 * 2, i / 3 % 3, i / 9 % 3 * 3, i / 27 + 4)) {
+            let line = Line { x1, y1, x2, y2 };
+            let reversed = Line {
+                x1: x2,
+                y1: y2,
+                x2: x1,
+                y2: y1,
+            };
+
+            // Lattice points are exactly the points on the segment
+            let on_line = |&(x, y): &(usize, usize)| {
+                (x as i64 - x1 as i64) * (y2 as i64 - y1 as i64)
+                    == (y as i64 - y1 as i64) * (x2 as i64 - x1 as i64)
+            };
+            let mut expected: Vec<(usize, usize)> = (x1.min(x2)..=x1.max(x2))
+                .flat_map(|x| (y1.min(y2)..=y1.max(y2)).map(move |y| (x, y)))
+                .filter(on_line)
+                .collect();
+            let mut lattice = line.points(Raster::LatticePoints);
+            expected.sort_unstable();
+            lattice.sort_unstable();
+            assert_eq!(lattice, expected);
+
+            // Covered cells are a superset that does not depend on the direction
+            let mut covered = line.points(Raster::CoveredCells);
+            let mut covered_reversed = reversed.points(Raster::CoveredCells);
+            covered.sort_unstable();
+            covered_reversed.sort_unstable();
+            assert_eq!(covered, covered_reversed);
+            assert!(lattice.iter().all(|p| covered.contains(p)));
+        }
+    }
+
     #[test]
     fn test_count_overlaps() {
         let mut grid = HashGrid::new();
@@ -171,8 +449,8 @@ mod tests {
             x2: 0,
             y2: 0,
         };
-        trace_line_on_grid(&line1, &mut grid);
-        trace_line_on_grid(&line1, &mut grid);
+        trace_line_on_grid(&line1, Raster::CoveredCells, &mut grid);
+        trace_line_on_grid(&line1, Raster::CoveredCells, &mut grid);
 
         // Diagonal
         let line3 = Line {
@@ -182,7 +460,7 @@ mod tests {
             y2: 3,
         };
 
-        trace_line_on_grid(&line3, &mut grid);
+        trace_line_on_grid(&line3, Raster::CoveredCells, &mut grid);
         let result = count_overlaps(&grid);
         assert_eq!(result, 6);
     }
@@ -201,6 +479,81 @@ mod tests {
         assert_eq!(overlaps, 12);
     }
 
+    fn count_rasterized(lines: &[Line], min_lines: usize) -> usize {
+        let mut grid = HashGrid::new();
+        for line in lines {
+            trace_line_on_grid(line, Raster::LatticePoints, &mut grid);
+        }
+        grid.iter().filter(|&(_, v)| *v >= min_lines).count()
+    }
+
+    #[test]
+    fn test_find_overlaps_analytic() {
+        let lines = parse::transform_iter(TEST_DATA.lines(), |l| Line::from_str(l)).unwrap();
+        assert_eq!(find_overlaps_analytic(&lines, 2), 12);
+        assert_eq!(
+            find_overlaps_analytic(&lines, 1),
+            count_rasterized(&lines, 1)
+        );
+        assert_eq!(
+            find_overlaps_analytic(&lines, 3),
+            count_rasterized(&lines, 3)
+        );
+
+        let straight: Vec<Line> = lines
+            .into_iter()
+            .filter(|line| line.x1 == line.x2 || line.y1 == line.y2)
+            .collect();
+        assert_eq!(find_overlaps_analytic(&straight, 2), 5);
+
+        // Far too many points to rasterize: two overlapping collinear lines, a diagonal through
+        // their overlap and a line of slope 2/3 that touches both with its end points
+        let lines = parse::transform_iter(
+            [
+                "0,5000000 -> 8000000,5000000",
+                "2000000,5000000 -> 9000000,5000000",
+                "0,0 -> 9000000,9000000",
+                "3000000,3000000 -> 6000000,5000000",
+            ]
+            .iter(),
+            |l| Line::from_str(l),
+        )
+        .unwrap();
+        // The collinear overlap holds 6000001 points, plus where the last line meets the diagonal
+        assert_eq!(find_overlaps_analytic(&lines, 2), 6000001 + 1);
+        // Within the overlap, the diagonal crosses at x = 5000000 and the last line ends at 6000000
+        assert_eq!(find_overlaps_analytic(&lines, 3), 2);
+        assert_eq!(find_overlaps_analytic(&lines, 4), 0);
+    }
+
+    #[test]
+    fn test_find_overlaps_analytic_random() {
+        let mut rng = XorShift64::new(5);
+        for _ in 0..200 {
+            let size = rng.range_inclusive(1, 12) as usize;
+            let lines: Vec<Line> = (0..rng.range_inclusive(1, 15))
+                .map(|_| {
+                    let mut coordinate = || rng.range_inclusive(0, size as u64) as usize;
+                    Line {
+                        x1: coordinate(),
+                        y1: coordinate(),
+                        x2: coordinate(),
+                        y2: coordinate(),
+                    }
+                })
+                .collect();
+
+            for min_lines in 1..=4 {
+                assert_eq!(
+                    find_overlaps_analytic(&lines, min_lines),
+                    count_rasterized(&lines, min_lines),
+                    "{:?}",
+                    lines
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_d05() {
         let lines = file_io::read_lines_as_structs("inputs/d05").unwrap();
@@ -209,5 +562,6 @@ mod tests {
 
         let all_overlaps = find_all_overlaps(&lines);
         println!("Day 05 result #2: {}", all_overlaps);
+        assert_eq!(find_overlaps_analytic(&lines, 2), all_overlaps);
     }
 }
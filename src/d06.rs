@@ -1,6 +1,14 @@
 #![allow(dead_code)]
+use crate::common::checked::{CheckedArithmetic, Modular};
+use crate::common::error::CommonError;
+use std::fmt::Display;
+use std::iter::Sum;
 use std::str::FromStr;
 
+const NUM_TIMERS: usize = 9;
+const RESET_TIMER: usize = 6;
+const NEW_TIMER: usize = 8;
+
 fn parse_data<S: AsRef<str>>(data: S) -> Vec<u8> {
     data.as_ref()
         .trim()
@@ -51,9 +59,288 @@ fn simulate_growth_large(fishes: &[u8], days: usize) -> usize {
     days_to_breed.iter().sum()
 }
 
+/// Age-structured population in the spirit of a Leslie model. Every individual carries a timer
+/// that counts down once per day. Adults at zero spawn `offspring` newborns and restart at
+/// `spawn_period - 1`. Newborns start `newborn_delay` days above that, plus a whole spawn period
+/// for each of the `maturation_stages` cycles they spend growing up before their first spawn.
+/// With mortality, every individual dies with that probability at the end of each day, after
+/// spawning, and counts become expected values.
+#[derive(Debug, Clone, PartialEq)]
+struct PopulationModel {
+    spawn_period: usize,
+    newborn_delay: usize,
+    maturation_stages: usize,
+    offspring: u64,
+    mortality: Option<f64>,
+}
+
+impl PopulationModel {
+    fn new(
+        spawn_period: usize,
+        newborn_delay: usize,
+        maturation_stages: usize,
+        offspring: u64,
+        mortality: Option<f64>,
+    ) -> Result<Self, CommonError> {
+        if spawn_period == 0 {
+            return Err(CommonError::Evaluation(
+                "Model spawn_period has to be at least one day".to_string(),
+            ));
+        }
+        if let Some(rate) = mortality.filter(|rate| !(0.0..=1.0).contains(rate)) {
+            return Err(CommonError::Evaluation(format!(
+                "Model mortality has to be a probability, got {}",
+                rate
+            )));
+        }
+        Ok(PopulationModel {
+            spawn_period,
+            newborn_delay,
+            maturation_stages,
+            offspring,
+            mortality,
+        })
+    }
+
+    fn lanternfish() -> Self {
+        PopulationModel::new(RESET_TIMER + 1, NEW_TIMER - RESET_TIMER, 0, 1, None).unwrap()
+    }
+
+    fn reset_timer(&self) -> usize {
+        self.spawn_period - 1
+    }
+
+    fn newborn_timer(&self) -> usize {
+        self.reset_timer() + self.newborn_delay + self.maturation_stages * self.spawn_period
+    }
+
+    fn num_timers(&self) -> usize {
+        self.reset_timer().max(self.newborn_timer()) + 1
+    }
+
+    // Starting timers may be above anything the model produces itself
+    fn histogram<T: Clone, F: Fn(u64) -> T>(&self, timers: &[u8], lift: F) -> Vec<T> {
+        let max_timer = timers.iter().max().map_or(0, |&t| t as usize);
+        let mut counts = vec![0; self.num_timers().max(max_timer + 1)];
+        for &timer in timers {
+            counts[timer as usize] += 1;
+        }
+        counts.into_iter().map(lift).collect()
+    }
+
+    fn step<T>(&self, histogram: &[T]) -> Option<Vec<T>>
+    where
+        T: CheckedArithmetic + Clone + From<u64>,
+    {
+        let mut next = histogram[1..].to_vec();
+        next.push(T::from(0));
+        let spawning = &histogram[0];
+        let newborns = spawning.checked_mul(&T::from(self.offspring))?;
+        next[self.reset_timer()] = next[self.reset_timer()].checked_add(spawning)?;
+        next[self.newborn_timer()] = next[self.newborn_timer()].checked_add(&newborns)?;
+        Some(next)
+    }
+
+    fn step_expected(&self, histogram: &[f64]) -> Vec<f64> {
+        let mut next = histogram[1..].to_vec();
+        next.push(0.0);
+        next[self.reset_timer()] += histogram[0];
+        next[self.newborn_timer()] += histogram[0] * self.offspring as f64;
+
+        let survival = 1.0 - self.mortality.unwrap_or(0.0);
+        next.iter_mut().for_each(|count| *count *= survival);
+        next
+    }
+
+    /// Exact histograms of the timer values for every day from 0 to `days`, both included. Only
+    /// for models without mortality.
+    fn simulate<T>(&self, timers: &[u8], days: usize) -> Result<Vec<Vec<T>>, CommonError>
+    where
+        T: CheckedArithmetic + Clone + From<u64>,
+    {
+        if self.mortality.is_some() {
+            return Err(CommonError::Evaluation(
+                "Counts with mortality are expected values, not exact".to_string(),
+            ));
+        }
+
+        let mut histograms = vec![self.histogram(timers, T::from)];
+        for day in 1..=days {
+            let next = self.step(histograms.last().unwrap()).ok_or_else(|| {
+                CommonError::Overflow(format!("Population overflowed on day {}", day))
+            })?;
+            histograms.push(next);
+        }
+        Ok(histograms)
+    }
+
+    /// Expected histograms of the timer values for every day from 0 to `days`, both included.
+    fn simulate_expected(&self, timers: &[u8], days: usize) -> Vec<Vec<f64>> {
+        let mut histograms = vec![self.histogram(timers, |count| count as f64)];
+        for _ in 0..days {
+            let next = self.step_expected(histograms.last().unwrap());
+            histograms.push(next);
+        }
+        histograms
+    }
+
+    /// Brute-force counterpart of [`PopulationModel::step`] that tracks every individual, for
+    /// models without mortality.
+    fn step_individuals(&self, timers: &mut Vec<usize>) {
+        assert!(self.mortality.is_none(), "Individuals cannot die partially");
+        let mut num_spawning = 0;
+        for timer in timers.iter_mut() {
+            if *timer == 0 {
+                num_spawning += 1;
+                *timer = self.reset_timer();
+            } else {
+                *timer -= 1;
+            }
+        }
+        let num_newborns = num_spawning * self.offspring as usize;
+        timers.extend(std::iter::repeat_n(self.newborn_timer(), num_newborns));
+    }
+}
+
+/// One row per day with the count for each timer value and the total.
+fn histograms_to_csv<T>(histograms: &[Vec<T>]) -> String
+where
+    T: Display + for<'a> Sum<&'a T>,
+{
+    let num_timers = histograms.first().map_or(0, |h| h.len());
+    let mut lines = Vec::with_capacity(histograms.len() + 1);
+
+    let mut header = vec!["day".to_string()];
+    header.extend((0..num_timers).map(|timer| format!("timer_{}", timer)));
+    header.push("total".to_string());
+    lines.push(header.join(","));
+
+    for (day, histogram) in histograms.iter().enumerate() {
+        let mut row = vec![day.to_string()];
+        row.extend(histogram.iter().map(|count| count.to_string()));
+        row.push(histogram.iter().sum::<T>().to_string());
+        lines.push(row.join(","));
+    }
+
+    lines.join("\n") + "\n"
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+// Maps timer counts of one day to the next: new[i] = sum of m[i][j] * old[j]. Timers count down by
+// one, and the fishes at zero both reset to 6 and spawn new ones at 8. `lift` turns plain numbers
+// into the count type.
+fn transition_matrix<T: Clone, F: Fn(u64) -> T>(lift: &F) -> Matrix<T> {
+    let mut m = vec![vec![lift(0); NUM_TIMERS]; NUM_TIMERS];
+    for timer in 0..NUM_TIMERS - 1 {
+        m[timer][timer + 1] = lift(1);
+    }
+    m[RESET_TIMER][0] = lift(1);
+    m[NEW_TIMER][0] = lift(1);
+    m
+}
+
+fn identity_matrix<T, F: Fn(u64) -> T>(lift: &F) -> Matrix<T> {
+    (0..NUM_TIMERS)
+        .map(|i| (0..NUM_TIMERS).map(|j| lift((i == j) as u64)).collect())
+        .collect()
+}
+
+fn overflow() -> CommonError {
+    CommonError::Overflow("Fish count overflowed while multiplying matrices".to_string())
+}
+
+fn mat_mul<T, F>(a: &Matrix<T>, b: &Matrix<T>, lift: &F) -> Result<Matrix<T>, CommonError>
+where
+    T: CheckedArithmetic + Clone,
+    F: Fn(u64) -> T,
+{
+    let mut result = vec![vec![lift(0); NUM_TIMERS]; NUM_TIMERS];
+    for (i, row) in result.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            for k in 0..NUM_TIMERS {
+                let product = a[i][k].checked_mul(&b[k][j]).ok_or_else(overflow)?;
+                *cell = cell.checked_add(&product).ok_or_else(overflow)?;
+            }
+        }
+    }
+    Ok(result)
+}
+
+// Exponentiation by squaring, O(log n) matrix products
+fn mat_pow<T, F>(m: &Matrix<T>, mut n: u64, lift: &F) -> Result<Matrix<T>, CommonError>
+where
+    T: CheckedArithmetic + Clone,
+    F: Fn(u64) -> T,
+{
+    let mut result = identity_matrix(lift);
+    let mut base = m.clone();
+    while n > 0 {
+        if n & 1 == 1 {
+            result = mat_mul(&result, &base, lift)?;
+        }
+        n >>= 1;
+        if n > 0 {
+            base = mat_mul(&base, &base, lift)?;
+        }
+    }
+    Ok(result)
+}
+
+fn timer_histogram(fishes: &[u8]) -> Result<[u64; NUM_TIMERS], CommonError> {
+    let mut histogram = [0; NUM_TIMERS];
+    for &fish in fishes {
+        *histogram
+            .get_mut(fish as usize)
+            .ok_or(CommonError::Parse("Fish timers go up to 8"))? += 1;
+    }
+    Ok(histogram)
+}
+
+// Number of fishes after `days` in whatever domain `lift` maps counts into
+fn count_fishes_with<T, F>(fishes: &[u8], days: u64, lift: F) -> Result<T, CommonError>
+where
+    T: CheckedArithmetic + Clone,
+    F: Fn(u64) -> T,
+{
+    let histogram = timer_histogram(fishes)?;
+    let overflowed = || CommonError::Overflow(format!("Fish count overflowed after {} days", days));
+    let m = mat_pow(&transition_matrix(&lift), days, &lift).map_err(|_| overflowed())?;
+
+    let mut total = lift(0);
+    for row in &m {
+        for (entry, &count) in row.iter().zip(histogram.iter()) {
+            let fishes = entry.checked_mul(&lift(count)).ok_or_else(overflowed)?;
+            total = total.checked_add(&fishes).ok_or_else(overflowed)?;
+        }
+    }
+    Ok(total)
+}
+
+/// Number of fishes after `days`, in O(log days) using the transition matrix.
+fn count_fishes_after<T>(fishes: &[u8], days: u64) -> Result<T, CommonError>
+where
+    T: CheckedArithmetic + Clone + From<u64>,
+{
+    count_fishes_with(fishes, days, T::from)
+}
+
+/// Number of fishes after `days` modulo `modulus`, which can be arbitrarily many days.
+fn count_fishes_mod(fishes: &[u8], days: u64, modulus: u64) -> Result<u64, CommonError> {
+    if modulus == 0 {
+        return Err(CommonError::Evaluation(
+            "Modulus has to be positive".to_string(),
+        ));
+    }
+    let count = count_fishes_with(fishes, days, |n| Modular::new(n, modulus))?;
+    Ok(count.value())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::big_uint::BigUint;
+    use crate::common::file_io;
     use std::fs::read_to_string;
 
     const TEST_DATA: &str = r"3,4,3,1,2";
@@ -95,6 +382,198 @@ mod tests {
         assert_eq!(result_after_256, 26984457539);
     }
 
+    #[test]
+    fn test_count_fishes_after() {
+        let fishes = parse_data(TEST_DATA);
+        for days in [0, 1, 18, 80, 256] {
+            assert_eq!(
+                count_fishes_after::<u128>(&fishes, days).unwrap(),
+                simulate_growth_large(&fishes, days as usize) as u128
+            );
+        }
+
+        // Roughly ten doublings every eighty days, so u128 gives up long before 2000
+        assert!(matches!(
+            count_fishes_after::<u128>(&fishes, 2000),
+            Err(CommonError::Overflow(_))
+        ));
+
+        // Bucket by bucket simulation with exact numbers to compare against
+        let mut buckets: Vec<BigUint> = timer_histogram(&fishes)
+            .unwrap()
+            .iter()
+            .map(|&c| BigUint::from(c))
+            .collect();
+        for _ in 0..2000 {
+            buckets.rotate_left(1);
+            buckets[RESET_TIMER] = &buckets[RESET_TIMER] + &buckets[NEW_TIMER];
+        }
+        let expected = buckets.iter().fold(BigUint::zero(), |acc, c| &acc + c);
+        let exact = count_fishes_after::<BigUint>(&fishes, 2000).unwrap();
+        assert_eq!(exact, expected);
+
+        for modulus in [2, 97, 1_000_000_007, u64::MAX] {
+            assert_eq!(
+                count_fishes_mod(&fishes, 2000, modulus).unwrap(),
+                exact.rem_u64(modulus)
+            );
+        }
+
+        // 10^12 = (10^6)^2, so both routes to the power must agree
+        const P: u64 = 1_000_000_007;
+        let lift = |n| Modular::new(n, P);
+        let m = transition_matrix(&lift);
+        let direct = mat_pow(&m, 1_000_000_000_000, &lift).unwrap();
+        let nested = mat_pow(&mat_pow(&m, 1_000_000, &lift).unwrap(), 1_000_000, &lift).unwrap();
+        assert_eq!(direct, nested);
+        assert!(count_fishes_mod(&fishes, 1_000_000_000_000, P).unwrap() < P);
+        assert!(matches!(
+            count_fishes_mod(&fishes, 10, 0),
+            Err(CommonError::Evaluation(_))
+        ));
+
+        assert!(matches!(
+            count_fishes_after::<u128>(&[3, 9], 10),
+            Err(CommonError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_population_model_lanternfish() {
+        let model = PopulationModel::lanternfish();
+        let fishes = parse_data(TEST_DATA);
+        let histograms = model.simulate::<u128>(&fishes, 256).unwrap();
+
+        // Against the original brute force, day by day
+        let mut reference = fishes.clone();
+        for histogram in histograms.iter().take(81) {
+            let mut expected = vec![0; NUM_TIMERS];
+            for &fish in &reference {
+                expected[fish as usize] += 1;
+            }
+            assert_eq!(histogram, &expected);
+            step(&mut reference);
+        }
+
+        let total_256: u128 = histograms[256].iter().sum();
+        assert_eq!(total_256, 26984457539);
+
+        // Well beyond what a f64 holds exactly
+        let histograms = model.simulate::<u128>(&fishes, 600).unwrap();
+        let total_600: u128 = histograms[600].iter().sum();
+        assert!(total_600 > 1 << 60);
+        assert_eq!(total_600, count_fishes_after::<u128>(&fishes, 600).unwrap());
+
+        assert!(matches!(
+            model.simulate::<u128>(&fishes, 2000),
+            Err(CommonError::Overflow(_))
+        ));
+        let histograms = model.simulate::<BigUint>(&fishes, 2000).unwrap();
+        let total_2000 = histograms[2000]
+            .iter()
+            .fold(BigUint::zero(), |acc, count| &acc + count);
+        assert_eq!(
+            total_2000,
+            count_fishes_after::<BigUint>(&fishes, 2000).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_population_model_configurations() {
+        let fishes = parse_data(TEST_DATA);
+        let configurations = [
+            (3, 0, 0, 1),
+            (5, 4, 0, 2),
+            (4, 1, 2, 1),
+            (1, 0, 0, 1),
+            (7, 2, 1, 3),
+        ];
+
+        for (spawn_period, newborn_delay, maturation_stages, offspring) in configurations {
+            let model = PopulationModel::new(
+                spawn_period,
+                newborn_delay,
+                maturation_stages,
+                offspring,
+                None,
+            )
+            .unwrap();
+            let histograms = model.simulate::<u128>(&fishes, 20).unwrap();
+
+            let mut individuals: Vec<usize> = fishes.iter().map(|&f| f as usize).collect();
+            for histogram in &histograms {
+                let mut expected = vec![0; histogram.len()];
+                for &timer in &individuals {
+                    expected[timer] += 1;
+                }
+                assert_eq!(histogram, &expected, "{:?}", model);
+                model.step_individuals(&mut individuals);
+            }
+
+            // Expected values without mortality are the exact counts
+            let expected = model.simulate_expected(&fishes, 20);
+            for (exact, expected) in histograms.iter().zip(&expected) {
+                let exact: Vec<f64> = exact.iter().map(|&count| count as f64).collect();
+                assert_eq!(&exact, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn test_population_model_new() {
+        assert!(matches!(
+            PopulationModel::new(0, 2, 0, 1, None),
+            Err(CommonError::Evaluation(msg)) if msg.contains("spawn_period")
+        ));
+        for rate in [-0.1, 1.5, f64::NAN] {
+            assert!(matches!(
+                PopulationModel::new(7, 2, 0, 1, Some(rate)),
+                Err(CommonError::Evaluation(msg)) if msg.contains("mortality")
+            ));
+        }
+        assert!(PopulationModel::new(7, 2, 0, 1, Some(0.0)).is_ok());
+        assert!(PopulationModel::new(7, 2, 0, 1, Some(1.0)).is_ok());
+    }
+
+    #[test]
+    fn test_population_model_mortality() {
+        let model = PopulationModel::new(7, 2, 0, 0, Some(0.5)).unwrap();
+        let histograms = model.simulate_expected(&[3, 4, 3, 1], 3);
+        let totals: Vec<f64> = histograms.iter().map(|h| h.iter().sum()).collect();
+        assert_eq!(totals, vec![4.0, 2.0, 1.0, 0.5]);
+        assert!(matches!(
+            model.simulate::<u128>(&[3, 4, 3, 1], 3),
+            Err(CommonError::Evaluation(_))
+        ));
+
+        let model = PopulationModel::new(7, 2, 0, 1, Some(1.0)).unwrap();
+        assert_eq!(
+            model.simulate_expected(&[0, 1], 1)[1].iter().sum::<f64>(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_histograms_to_csv() {
+        let model = PopulationModel::lanternfish();
+        let csv = histograms_to_csv(&model.simulate::<u128>(&[1, 0], 2).unwrap());
+        assert_eq!(
+            csv,
+            "day,timer_0,timer_1,timer_2,timer_3,timer_4,timer_5,timer_6,timer_7,timer_8,total\n\
+             0,1,1,0,0,0,0,0,0,0,2\n\
+             1,1,0,0,0,0,0,1,0,1,3\n\
+             2,0,0,0,0,0,1,1,1,1,4\n"
+        );
+        let expected_csv = histograms_to_csv(&model.simulate_expected(&[1, 0], 2));
+        assert_eq!(expected_csv, csv);
+
+        let file_name = format!("d06_test_histograms_{}.csv", std::process::id());
+        let path = std::env::temp_dir().join(file_name);
+        file_io::write_string(&path, &csv).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), csv);
+        std::fs::remove_file(&path).unwrap();
+    }
+
     #[test]
     fn test_d06() {
         let data = read_to_string("inputs/d06").unwrap();
@@ -104,5 +583,8 @@ mod tests {
 
         let result_large = simulate_growth_large(&fishes, 256);
         println!("Day 06 result #2; {}", result_large);
+
+        let result_huge = count_fishes_mod(&fishes, 1_000_000_000_000, 1_000_000_007).unwrap();
+        println!("Day 06 after 10^12 days mod 10^9+7: {}", result_huge);
     }
 }
@@ -1,4 +1,6 @@
 #![allow(dead_code)]
+use crate::common::error::CommonError;
+use crate::common::parse::transform_iter;
 use std::cmp::*;
 use std::str::FromStr;
 
@@ -10,6 +12,37 @@ fn parse_data<S: AsRef<str>>(s: S) -> Vec<u64> {
         .collect()
 }
 
+/// Crab at a position with one coordinate per axis. The weight scales its fuel, so a crab of
+/// weight 3 costs as much as three crabs in the same spot.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Crab<const D: usize> {
+    position: [u64; D],
+    weight: u64,
+}
+
+/// Same format as a single crab in [`parse_data`], with the coordinates of further axes separated
+/// by `;` and an optional `*weight` suffix, e.g. `16;3*2`.
+impl<const D: usize> FromStr for Crab<D> {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (coordinates, weight) = match s.split_once('*') {
+            Some((coordinates, weight)) => (coordinates, weight.parse()?),
+            None => (s, 1),
+        };
+
+        let coordinates = transform_iter(coordinates.split(';'), |c| c.parse::<u64>())?;
+        let position = coordinates
+            .try_into()
+            .map_err(|_| CommonError::Parse("Wrong number of coordinates for a crab"))?;
+        Ok(Crab { position, weight })
+    }
+}
+
+fn parse_crabs<const D: usize, S: AsRef<str>>(s: S) -> Result<Vec<Crab<D>>, CommonError> {
+    transform_iter(s.as_ref().trim().split(','), |crab| crab.parse())
+}
+
 fn compute_median(numbers: &[u64]) -> u64 {
     let mut sorted = numbers.to_vec();
     sorted.sort_unstable();
@@ -23,55 +56,103 @@ fn compute_total_distance_to_median(positions: &[u64]) -> u64 {
         .fold(0, |acc, &x| acc + max(median, x) - min(median, x))
 }
 
-fn compute_nonconstant_fuel_usage(x: u64, y: u64) -> u64 {
-    let diff = max(x, y) - min(x, y);
-    (diff * (diff + 1)) / 2 // Gauss addition
+/// Fuel for moving `distance` steps when every step costs one more than the previous one.
+fn triangular_fuel(distance: u64) -> u64 {
+    distance * (distance + 1) / 2 // Gauss addition
 }
 
-fn compute_total_nonconstant_fuel_spent(positions: &[u64], target: u64) -> u64 {
-    positions
-        .iter()
-        .fold(0, |acc, &x| acc + compute_nonconstant_fuel_usage(target, x))
+/// Minimum of a convex function over `lo..=hi`, as `(position, value)`. Binary search for the
+/// first position where the discrete derivative `f(x + 1) - f(x)` is no longer negative, so ties
+/// go to the leftmost optimum.
+fn minimize_convex<F>(lo: u64, hi: u64, f: F) -> (u64, u64)
+where
+    F: Fn(u64) -> u64,
+{
+    assert!(lo <= hi, "Empty search range");
+    let (mut lo, mut hi) = (lo, hi);
+    while lo < hi {
+        let mid = lo + (hi - lo) / 2;
+        if f(mid + 1) < f(mid) {
+            lo = mid + 1;
+        } else {
+            hi = mid;
+        }
+    }
+    (lo, f(lo))
 }
 
-fn compute_optimum_total_distance_nonconstant(positions: &[u64]) -> u64 {
-    // Not sure if there is a closed form solution for this
-    // but the optimization surface should be convex since there is only a single minimum
-    // We can do a numerical derivative and use a gradient descentish algorithm
-    // Assume median is a good place to start
-    let mut best_pos = compute_median(positions);
-    let mut prev = compute_total_nonconstant_fuel_spent(positions, best_pos as u64) as f64;
-    let mut prev_d: Option<f64> = None;
-    let alpha = 2.0 * prev.sqrt() / prev; // Heuristic
-                                          // let alpha = *positions.iter().max().unwrap() as f64 / prev.sqrt(); // Heuristic
-    loop {
-        // We would like to go until our gradient flips its sign and is abs() <= 1.5
-        let up = compute_total_nonconstant_fuel_spent(positions, best_pos + 1) as f64;
-        let mut grad = alpha * (up - prev);
-
-        // Prevent getting stuck on the same pos
-        // Rounds down if neg, up if pos
-        if grad.abs() < 1.0 {
-            grad = grad.signum()
-        }
-        let new_best_pos = (best_pos as f64 - grad) as u64;
+/// Total fuel for crabs given as `(position, weight)` to meet at `target`, given the fuel for
+/// moving one crab a distance.
+fn compute_weighted_fuel<I, F>(crabs: I, target: u64, crab_fuel: F) -> u64
+where
+    I: IntoIterator<Item = (u64, u64)>,
+    F: Fn(u64) -> u64,
+{
+    crabs
+        .into_iter()
+        .map(|(x, weight)| weight * crab_fuel(max(target, x) - min(target, x)))
+        .sum()
+}
 
-        let cur = compute_total_nonconstant_fuel_spent(positions, new_best_pos) as f64;
+fn compute_total_fuel<F>(positions: &[u64], target: u64, crab_fuel: F) -> u64
+where
+    F: Fn(u64) -> u64,
+{
+    compute_weighted_fuel(positions.iter().map(|&x| (x, 1)), target, crab_fuel)
+}
 
-        if prev_d.is_some() && prev_d.unwrap().signum() != grad.signum() && grad.abs() < 1.5 {
-            break;
-        }
-        prev = cur;
-        prev_d = Some(grad);
-        best_pos = new_best_pos;
+/// Best meeting position and its total fuel for any convex, non-decreasing per-crab fuel, or None
+/// without crabs. A sum of convex functions is convex, and the optimum never lies outside the
+/// crabs' positions.
+fn find_optimum<F>(positions: &[u64], crab_fuel: F) -> Option<(u64, u64)>
+where
+    F: Fn(u64) -> u64,
+{
+    let lo = *positions.iter().min()?;
+    let hi = *positions.iter().max()?;
+    Some(minimize_convex(lo, hi, |target| {
+        compute_total_fuel(positions, target, &crab_fuel)
+    }))
+}
+
+/// Best meeting point and its total fuel when a crab pays `crab_fuel` separately for its distance
+/// along every axis, Manhattan style, or None without crabs. The axes do not interact, so each is
+/// minimized on its own.
+fn find_weighted_optimum<const D: usize, F>(
+    crabs: &[Crab<D>],
+    crab_fuel: F,
+) -> Option<([u64; D], u64)>
+where
+    F: Fn(u64) -> u64,
+{
+    if crabs.is_empty() {
+        return None;
+    }
+
+    let mut meeting_point = [0; D];
+    let mut total_fuel = 0;
+    for (axis, coordinate) in meeting_point.iter_mut().enumerate() {
+        let axis_crabs = || crabs.iter().map(|crab| (crab.position[axis], crab.weight));
+        let lo = axis_crabs().map(|(x, _)| x).min()?;
+        let hi = axis_crabs().map(|(x, _)| x).max()?;
+        let (position, fuel) = minimize_convex(lo, hi, |target| {
+            compute_weighted_fuel(axis_crabs(), target, &crab_fuel)
+        });
+        *coordinate = position;
+        total_fuel += fuel;
     }
+    Some((meeting_point, total_fuel))
+}
 
-    prev.round() as u64
+fn compute_optimum_total_distance_nonconstant(positions: &[u64]) -> u64 {
+    // No crabs need no fuel
+    find_optimum(positions, triangular_fuel).map_or(0, |(_, fuel)| fuel)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::common::rng::XorShift64;
     use std::fs::read_to_string;
 
     const TEST_DATA: &str = r"16,1,2,0,4,2,7,1,2,14";
@@ -97,6 +178,133 @@ mod tests {
         assert_eq!(distance, 168);
     }
 
+    #[test]
+    fn test_find_optimum() {
+        let data = parse_data(TEST_DATA);
+        assert_eq!(find_optimum(&data, |d| d), Some((2, 37)));
+        assert_eq!(find_optimum(&data, triangular_fuel), Some((5, 168)));
+        assert_eq!(find_optimum(&[7], triangular_fuel), Some((7, 0)));
+        assert_eq!(find_optimum(&[], triangular_fuel), None);
+        assert_eq!(compute_optimum_total_distance_nonconstant(&[]), 0);
+        // Flat stretches of the total pick the leftmost optimum
+        assert_eq!(find_optimum(&[0, 10], |d| d), Some((0, 10)));
+    }
+
+    #[test]
+    fn test_find_optimum_random() {
+        let crab_fuels: [fn(u64) -> u64; 4] = [
+            |d| d,
+            triangular_fuel,
+            |d| 3 * d * d + d,
+            |d| d.saturating_sub(4),
+        ];
+        let mut rng = XorShift64::new(7);
+
+        for _ in 0..200 {
+            let num_crabs = rng.range_inclusive(1, 20) as usize;
+            let spread = rng.range_inclusive(0, 200);
+            let positions: Vec<u64> = (0..num_crabs)
+                .map(|_| rng.range_inclusive(0, spread))
+                .collect();
+
+            let lo = *positions.iter().min().unwrap();
+            let hi = *positions.iter().max().unwrap();
+            for crab_fuel in crab_fuels {
+                let total = |target| compute_total_fuel(&positions, target, crab_fuel);
+                let (fuel, position) = (lo..=hi).map(|t| (total(t), t)).min().unwrap();
+                assert_eq!(find_optimum(&positions, crab_fuel), Some((position, fuel)));
+                // Nothing outside the crabs' span does better
+                assert_eq!((0..=spread + 10).map(total).min().unwrap(), fuel);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_crabs() {
+        let crabs: Vec<Crab<1>> = parse_crabs(TEST_DATA).unwrap();
+        let positions: Vec<u64> = crabs.iter().map(|crab| crab.position[0]).collect();
+        assert_eq!(positions, parse_data(TEST_DATA));
+        assert!(crabs.iter().all(|crab| crab.weight == 1));
+
+        let crabs: Vec<Crab<3>> = parse_crabs("1;2;3*4,5;6;7\n").unwrap();
+        assert_eq!(
+            crabs,
+            vec![
+                Crab {
+                    position: [1, 2, 3],
+                    weight: 4
+                },
+                Crab {
+                    position: [5, 6, 7],
+                    weight: 1
+                }
+            ]
+        );
+
+        assert!(parse_crabs::<2, _>("1;2,3").is_err());
+        assert!(parse_crabs::<1, _>("1*x").is_err());
+    }
+
+    #[test]
+    fn test_find_weighted_optimum() {
+        // Unit weights in 1D are the original puzzle
+        let crabs: Vec<Crab<1>> = parse_crabs(TEST_DATA).unwrap();
+        assert_eq!(find_weighted_optimum(&crabs, |d| d), Some(([2], 37)));
+        assert_eq!(
+            find_weighted_optimum(&crabs, triangular_fuel),
+            Some(([5], 168))
+        );
+
+        // A weight is the same as repeating the crab
+        let crabs: Vec<Crab<1>> = parse_crabs("0*3,10").unwrap();
+        let (position, fuel) = find_optimum(&[0, 0, 0, 10], triangular_fuel).unwrap();
+        assert_eq!(
+            find_weighted_optimum(&crabs, triangular_fuel),
+            Some(([position], fuel))
+        );
+
+        let crabs: Vec<Crab<2>> = parse_crabs("0;0,4;0*3,0;6").unwrap();
+        assert_eq!(find_weighted_optimum(&crabs, |d| d), Some(([4, 0], 14)));
+        assert_eq!(find_weighted_optimum::<2, _>(&[], |d| d), None);
+    }
+
+    #[test]
+    fn test_find_weighted_optimum_random() {
+        let crab_fuels: [fn(u64) -> u64; 3] = [|d| d, triangular_fuel, |d| d * d * d];
+        let mut rng = XorShift64::new(45);
+
+        for _ in 0..50 {
+            let crabs: Vec<Crab<3>> = (0..rng.range_inclusive(1, 8))
+                .map(|_| Crab {
+                    position: [0; 3].map(|_| rng.range_inclusive(0, 12)),
+                    weight: rng.range_inclusive(0, 5),
+                })
+                .collect();
+
+            for crab_fuel in crab_fuels {
+                let total = |target: [u64; 3]| -> u64 {
+                    crabs
+                        .iter()
+                        .map(|crab| {
+                            let fuel: u64 = (0..3)
+                                .map(|axis| crab_fuel(crab.position[axis].abs_diff(target[axis])))
+                                .sum();
+                            crab.weight * fuel
+                        })
+                        .sum()
+                };
+                let brute_force = (0..13 * 13 * 13)
+                    .map(|i| total([i % 13, i / 13 % 13, i / 169]))
+                    .min()
+                    .unwrap();
+
+                let (meeting_point, fuel) = find_weighted_optimum(&crabs, crab_fuel).unwrap();
+                assert_eq!(fuel, brute_force, "{:?}", crabs);
+                assert_eq!(total(meeting_point), fuel);
+            }
+        }
+    }
+
     #[test]
     fn test_d07() {
         let data = read_to_string("inputs/d07").unwrap();
@@ -1,82 +1,99 @@
 #![allow(dead_code)]
-use crate::common::algorithms;
-use crate::common::collections::dense_grid::{ArrayGrid, DenseGrid};
-
-fn flash(grid: &mut ArrayGrid<i32>, x: usize, y: usize) -> usize {
-    let fill_fn = |grid: &mut ArrayGrid<i32>, x: usize, y: usize| {
-        let cur = grid.get_pos(x, y);
-        if *cur <= 9 {
-            return None;
-        }
-        // Hax
-        grid.set_pos(x, y, i32::MIN);
-        let neighbours = grid.get_neighbours(x, y);
-        for (x_n, y_n) in neighbours {
-            let cur_neigh = *grid.get_pos(x_n, y_n);
-            if cur_neigh >= 0 {
-                grid.set_pos(x_n, y_n, cur_neigh + 1);
-            }
-        }
-
-        Some(1)
-    };
-
-    let expand_fn = |grid: &ArrayGrid<i32>, x: usize, y: usize| {
-        let neighbours = grid.get_neighbours(x, y);
-
-        neighbours
-            .iter()
-            .filter(|&&(x_n, y_n)| {
-                let cur_neigh = grid.get_pos(x_n, y_n);
-                *cur_neigh > 9
-            })
-            .cloned()
-            .collect()
-    };
-
-    algorithms::grid::generic_flood_fill_mut(grid, x, y, fill_fn, expand_fn)
+use crate::common::algorithms::automaton::{Automaton, Boundary};
+use crate::common::collections::dense_grid::ArrayGrid;
+use crate::common::collections::NEIGHBOURS_2D;
+use crate::common::error::CommonError;
+use std::collections::HashMap;
+
+type Octopuses = Automaton<ArrayGrid<i32>, i32>;
+
+// Only a safety net, the search for a synchronized step ends once the energies repeat
+const SYNC_STEP_BUDGET: usize = 100_000;
+
+// Marks an octopus that already flashed during the current step
+const FLASHED: i32 = -1;
+
+/// The octopus grid and the number of puzzle steps taken. A puzzle step runs several automaton
+/// steps, one per cascade round plus charging and resetting, so `octopuses.steps` counts rounds.
+#[derive(Clone)]
+struct Cavern {
+    octopuses: Octopuses,
+    steps: usize,
 }
 
-fn step(grid: &mut ArrayGrid<i32>) -> usize {
-    for e in grid.underlying.iter_mut() {
-        *e += 1;
+impl Cavern {
+    fn new(grid: ArrayGrid<i32>) -> Self {
+        Cavern {
+            octopuses: Automaton::dense(grid, &NEIGHBOURS_2D, Boundary::Fixed(0)),
+            steps: 0,
+        }
     }
 
-    let mut num_flashes = 0;
-    for y in 0..grid.y_size {
-        for x in 0..grid.x_size {
-            if *grid.get_pos(x, y) >= 9 {
-                num_flashes += flash(grid, x, y);
-            }
-        }
+    fn grid(&self) -> &ArrayGrid<i32> {
+        &self.octopuses.grid
     }
+}
 
-    for e in grid.underlying.iter_mut() {
-        if *e < 0 {
-            *e = 0;
-        }
+// Each cascade round every charged octopus flashes at once, and the rest gain one energy per
+// neighbour flashing in the same round. Flashed ones stay put until the step is over.
+fn cascade(cur: &i32, neighbours: &[i32]) -> i32 {
+    if *cur == FLASHED || *cur > 9 {
+        FLASHED
+    } else {
+        cur + neighbours.iter().filter(|&&n| n > 9).count() as i32
     }
+}
+
+fn step(cavern: &mut Cavern) -> usize {
+    let octopuses = &mut cavern.octopuses;
+    octopuses.step(|cur, _| cur + 1);
+
+    // Every round flashes at least one octopus, so there cannot be more rounds than cells
+    let num_cells = octopuses.grid.underlying.len();
+    octopuses.run_until_stable(cascade, num_cells + 1);
+
+    let num_flashes = octopuses
+        .grid
+        .underlying
+        .iter()
+        .filter(|&&e| e == FLASHED)
+        .count();
+    octopuses.step(|&cur, _| if cur == FLASHED { 0 } else { cur });
 
+    cavern.steps += 1;
     num_flashes
 }
 
-fn iterate(grid: &mut ArrayGrid<i32>, num_steps: usize) -> usize {
+fn iterate(cavern: &mut Cavern, num_steps: usize) -> usize {
     let mut num_flashes = 0;
     for _ in 0..num_steps {
-        num_flashes += step(grid);
+        num_flashes += step(cavern);
     }
     num_flashes
 }
 
-fn find_sync(grid: &mut ArrayGrid<i32>) -> usize {
-    let mut step_num = 0;
-    loop {
-        step(grid);
-        step_num += 1;
-        if grid.underlying.iter().all(|e| *e == 0) {
-            return step_num;
+/// First step on which every octopus flashes, taking at most `budget` steps. The energies evolve
+/// deterministically, so once they repeat without all octopuses having flashed together, they
+/// never will.
+fn find_sync(cavern: &Cavern, budget: usize) -> Result<usize, CommonError> {
+    let mut cavern = cavern.clone();
+    let mut seen = HashMap::from([(cavern.grid().underlying.clone(), cavern.steps)]);
+
+    for _ in 0..budget {
+        step(&mut cavern);
+        let energies = &cavern.grid().underlying;
+        if energies.iter().all(|&e| e == 0) {
+            return Ok(cavern.steps);
+        }
+        if let Some(start) = seen.insert(energies.clone(), cavern.steps) {
+            return Err(CommonError::Evaluation(format!(
+                "Octopuses repeat every {} steps from step {} without ever flashing together",
+                cavern.steps - start,
+                start
+            )));
         }
     }
+    Err(CommonError::StepBudget(budget))
 }
 
 #[cfg(test)]
@@ -108,7 +125,7 @@ mod tests {
 5957959665
 6394862637";
 
-        let mut grid: ArrayGrid<i32> = large_data.parse().unwrap();
+        let mut cavern = Cavern::new(large_data.parse().unwrap());
         let expected = r"8807476555
 5089087054
 8597889608
@@ -121,34 +138,58 @@ mod tests {
 8700006848";
 
         let expected_grid: ArrayGrid<i32> = expected.parse().unwrap();
-        let num_flashes = step(&mut grid);
+        let num_flashes = step(&mut cavern);
         assert_eq!(num_flashes, 35);
-        assert_eq!(grid, expected_grid);
+        assert_eq!(cavern.grid(), &expected_grid);
+        assert_eq!(cavern.steps, 1);
     }
 
     #[test]
     fn test_iterate() {
-        let mut grid: ArrayGrid<i32> = TEST_DATA.parse().unwrap();
-        let num_flashes = iterate(&mut grid, 10);
+        let mut cavern = Cavern::new(TEST_DATA.parse().unwrap());
+        let num_flashes = iterate(&mut cavern, 10);
         assert_eq!(num_flashes, 204);
+        assert_eq!(cavern.steps, 10);
+        assert!(cavern.octopuses.steps > 10);
     }
 
     #[test]
     fn test_find_sync() {
-        let mut grid: ArrayGrid<i32> = TEST_DATA.parse().unwrap();
-        let sync_step = find_sync(&mut grid);
-        assert_eq!(sync_step, 195);
+        let mut cavern = Cavern::new(TEST_DATA.parse().unwrap());
+        assert!(matches!(
+            find_sync(&cavern, 194),
+            Err(CommonError::StepBudget(194))
+        ));
+        assert_eq!(find_sync(&cavern, 195).unwrap(), 195);
+        assert_eq!(find_sync(&cavern, SYNC_STEP_BUDGET).unwrap(), 195);
+
+        // Once synchronized, they flash together again a full cycle later
+        iterate(&mut cavern, 195);
+        assert_eq!(find_sync(&cavern, SYNC_STEP_BUDGET).unwrap(), 195 + 10);
+        assert_eq!(find_sync(&cavern, 10).unwrap(), 195 + 10);
+    }
+
+    #[test]
+    fn test_find_sync_never() {
+        let cavern = Cavern::new("00\n23".parse().unwrap());
+        match find_sync(&cavern, SYNC_STEP_BUDGET) {
+            Err(CommonError::Evaluation(msg)) => assert!(msg.contains("without ever")),
+            other => panic!(
+                "Expected the octopuses to never synchronize, got {:?}",
+                other
+            ),
+        }
     }
 
     #[test]
     fn test_d11() {
         let data = read_to_string("inputs/d11").unwrap();
-        let mut grid: ArrayGrid<i32> = data.parse().unwrap();
+        let grid: ArrayGrid<i32> = data.parse().unwrap();
 
-        let num_flashes = iterate(&mut grid.clone(), 100);
+        let num_flashes = iterate(&mut Cavern::new(grid.clone()), 100);
         println!("Day 11 result #1: {}", num_flashes);
 
-        let sync_step = find_sync(&mut grid);
+        let sync_step = find_sync(&Cavern::new(grid), SYNC_STEP_BUDGET).unwrap();
         println!("Day 11 result #2: {}", sync_step);
     }
 }
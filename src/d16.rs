@@ -1,3 +1,5 @@
+use crate::common::big_uint::BigUint;
+use crate::common::checked::CheckedArithmetic;
 use crate::common::error::CommonError;
 
 #[derive(PartialEq, Debug)]
@@ -10,7 +12,7 @@ struct ParseResult {
 enum Packet {
     Literal {
         version: u64,
-        num: u64,
+        num: BigUint,
     },
     Operator {
         version: u64,
@@ -69,14 +71,17 @@ fn parse_bits(bits: &[char]) -> Packet {
         let type_id = binary_to_decimal(&bits[3..6]);
 
         if type_id == PacketType::Literal as u64 {
+            // Literals can be arbitrarily wide, so keep all of their bits
             let mut cur_pos = 6;
-            let mut num: u64 = 0;
-            while bits[cur_pos] != '0' {
-                num = (num << 4) | binary_to_decimal(&bits[cur_pos + 1..cur_pos + 5]);
+            let mut num_bits = Vec::new();
+            loop {
+                num_bits.extend_from_slice(&bits[cur_pos + 1..cur_pos + 5]);
                 cur_pos += 5;
+                if bits[cur_pos - 5] == '0' {
+                    break;
+                }
             }
-            num = (num << 4) | binary_to_decimal(&bits[cur_pos + 1..cur_pos + 5]);
-            cur_pos += 5;
+            let num = BigUint::from_bits(num_bits.iter().map(|&bit| bit == '1'));
 
             ParseResult {
                 result: Packet::Literal { version, num },
@@ -145,75 +150,119 @@ fn sum_versions(packet: &Packet) -> u64 {
     recursive_sum(packet)
 }
 
-fn process_packet(packet: &Packet) -> u64 {
-    match packet {
-        Packet::Literal { num, .. } => *num as u64,
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::Sum => sub_packets.iter().map(|p| process_packet(p)).sum(),
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::Product => {
-            sub_packets.iter().map(|p| process_packet(p)).product()
-        }
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::Minimum => {
-            sub_packets.iter().map(|p| process_packet(p)).min().unwrap()
-        }
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::Maximum => {
-            sub_packets.iter().map(|p| process_packet(p)).max().unwrap()
-        }
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::GreaterThan => {
-            let mut results = sub_packets.iter().map(|p| process_packet(p));
-            if results.next().unwrap() > results.next().unwrap() {
-                1
-            } else {
-                0
+/// Numeric domain a packet tree can be evaluated in.
+trait PacketValue: CheckedArithmetic + From<u64> + Ord + Clone {
+    /// None if the literal does not fit.
+    fn from_literal(num: &BigUint) -> Option<Self>;
+}
+
+impl PacketValue for u64 {
+    fn from_literal(num: &BigUint) -> Option<Self> {
+        num.to_u64()
+    }
+}
+
+impl PacketValue for u128 {
+    fn from_literal(num: &BigUint) -> Option<Self> {
+        num.to_u128()
+    }
+}
+
+impl PacketValue for BigUint {
+    fn from_literal(num: &BigUint) -> Option<Self> {
+        Some(num.clone())
+    }
+}
+
+// Path of sub-packet indices from the outermost packet, e.g. "root/2/0"
+fn format_path(path: &[usize]) -> String {
+    path.iter()
+        .fold("root".to_string(), |acc, i| format!("{}/{}", acc, i))
+}
+
+fn evaluate<T: PacketValue>(packet: &Packet) -> Result<T, CommonError> {
+    fn evaluate_at<T: PacketValue>(
+        packet: &Packet,
+        path: &mut Vec<usize>,
+    ) -> Result<T, CommonError> {
+        let (type_id, sub_packets) = match packet {
+            Packet::Literal { num, .. } => {
+                return T::from_literal(num).ok_or_else(|| {
+                    CommonError::Overflow(format!(
+                        "Literal of {} bits at {} overflows {}",
+                        num.bits(),
+                        format_path(path),
+                        std::any::type_name::<T>()
+                    ))
+                })
             }
+            Packet::Operator {
+                type_id,
+                sub_packets,
+                ..
+            } => (type_id, sub_packets),
+        };
+
+        let mut values: Vec<T> = Vec::with_capacity(sub_packets.len());
+        for (i, sub_packet) in sub_packets.iter().enumerate() {
+            path.push(i);
+            values.push(evaluate_at(sub_packet, path)?);
+            path.pop();
         }
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::LessThan => {
-            let mut results = sub_packets.iter().map(|p| process_packet(p));
-            if results.next().unwrap() < results.next().unwrap() {
-                1
-            } else {
-                0
+
+        let arity_error = |expected: &str| {
+            CommonError::Evaluation(format!(
+                "{:?} packet at {} expects {} operands, got {}",
+                type_id,
+                format_path(path),
+                expected,
+                values.len()
+            ))
+        };
+        let overflow_error = || {
+            CommonError::Overflow(format!(
+                "{:?} packet at {} overflowed",
+                type_id,
+                format_path(path)
+            ))
+        };
+
+        match type_id {
+            PacketType::Sum | PacketType::Product | PacketType::Minimum | PacketType::Maximum
+                if values.is_empty() =>
+            {
+                Err(arity_error("at least 1"))
             }
-        }
-        Packet::Operator {
-            type_id,
-            sub_packets,
-            ..
-        } if *type_id == PacketType::EqualTo => {
-            let mut results = sub_packets.iter().map(|p| process_packet(p));
-            if results.next().unwrap() == results.next().unwrap() {
-                1
-            } else {
-                0
+            PacketType::Sum => values[1..]
+                .iter()
+                .try_fold(values[0].clone(), |acc, v| acc.checked_add(v))
+                .ok_or_else(overflow_error),
+            PacketType::Product => values[1..]
+                .iter()
+                .try_fold(values[0].clone(), |acc, v| acc.checked_mul(v))
+                .ok_or_else(overflow_error),
+            PacketType::Minimum => Ok(values.iter().min().unwrap().clone()),
+            PacketType::Maximum => Ok(values.iter().max().unwrap().clone()),
+            PacketType::GreaterThan | PacketType::LessThan | PacketType::EqualTo
+                if values.len() != 2 =>
+            {
+                Err(arity_error("exactly 2"))
             }
-        }
-        _ => {
-            panic!()
+            PacketType::GreaterThan => Ok(T::from((values[0] > values[1]) as u64)),
+            PacketType::LessThan => Ok(T::from((values[0] < values[1]) as u64)),
+            PacketType::EqualTo => Ok(T::from((values[0] == values[1]) as u64)),
+            PacketType::Literal => Err(CommonError::Evaluation(format!(
+                "Operator packet at {} has literal type",
+                format_path(path)
+            ))),
         }
     }
+
+    evaluate_at(packet, &mut Vec::new())
+}
+
+fn process_packet(packet: &Packet) -> Result<u64, CommonError> {
+    evaluate(packet)
 }
 
 #[cfg(test)]
@@ -229,7 +278,7 @@ mod tests {
             result,
             Packet::Literal {
                 version: 6,
-                num: 2021
+                num: BigUint::from(2021u64)
             }
         );
     }
@@ -249,11 +298,11 @@ mod tests {
                 sub_packets: vec![
                     Packet::Literal {
                         version: 6,
-                        num: 10,
+                        num: BigUint::from(10u64),
                     },
                     Packet::Literal {
                         version: 2,
-                        num: 20
+                        num: BigUint::from(20u64)
                     }
                 ]
             }
@@ -273,9 +322,18 @@ mod tests {
                 type_id: 3.try_into().unwrap(),
                 length_type_id: 1,
                 sub_packets: vec![
-                    Packet::Literal { version: 2, num: 1 },
-                    Packet::Literal { version: 4, num: 2 },
-                    Packet::Literal { version: 1, num: 3 },
+                    Packet::Literal {
+                        version: 2,
+                        num: BigUint::from(1u64)
+                    },
+                    Packet::Literal {
+                        version: 4,
+                        num: BigUint::from(2u64)
+                    },
+                    Packet::Literal {
+                        version: 1,
+                        num: BigUint::from(3u64)
+                    },
                 ]
             }
         );
@@ -306,7 +364,7 @@ mod tests {
         fn process_helper(data: &str) -> u64 {
             let bits = hexadecimal_str_to_binary(&data);
             let packet = parse_bits(&bits);
-            process_packet(&packet)
+            process_packet(&packet).unwrap()
         }
         let data1 = "C200B40A82";
         assert_eq!(process_helper(data1), 3);
@@ -330,6 +388,126 @@ mod tests {
         assert_eq!(process_helper(data7), 1);
     }
 
+    #[test]
+    fn test_evaluate_overflow() {
+        let literal = |num: u64| Packet::Literal {
+            version: 0,
+            num: BigUint::from(num),
+        };
+        let operator = |type_id, sub_packets| Packet::Operator {
+            version: 0,
+            type_id,
+            length_type_id: 1,
+            sub_packets,
+        };
+
+        // max(1, u64::MAX * u64::MAX)
+        let packet = operator(
+            PacketType::Maximum,
+            vec![
+                literal(1),
+                operator(
+                    PacketType::Product,
+                    vec![literal(u64::MAX), literal(u64::MAX)],
+                ),
+            ],
+        );
+
+        match evaluate::<u64>(&packet) {
+            Err(CommonError::Overflow(msg)) => assert!(msg.contains("root/1")),
+            other => panic!("Expected overflow, got {:?}", other),
+        }
+        assert_eq!(
+            evaluate::<u128>(&packet).unwrap(),
+            u64::MAX as u128 * u64::MAX as u128
+        );
+
+        let packet = operator(
+            PacketType::Product,
+            vec![literal(u64::MAX), literal(u64::MAX), literal(u64::MAX)],
+        );
+        assert!(matches!(
+            evaluate::<u128>(&packet),
+            Err(CommonError::Overflow(_))
+        ));
+        assert_eq!(
+            evaluate::<BigUint>(&packet).unwrap().to_string(),
+            "6277101735386680762814942322444851025767571854389858533375"
+        );
+    }
+
+    #[test]
+    fn test_wide_literal() {
+        // Sum of a single 80 bit literal 0xFFFF...F, split into twenty 4 bit groups
+        let literal_bits = "1111".repeat(20);
+        let groups: Vec<String> = literal_bits
+            .as_bytes()
+            .chunks(4)
+            .enumerate()
+            .map(|(i, group)| {
+                let prefix = if i == 19 { '0' } else { '1' };
+                format!("{}{}", prefix, std::str::from_utf8(group).unwrap())
+            })
+            .collect();
+        let literal = format!("000100{}", groups.concat());
+        let packet_bits = format!("0000001{:011b}{}", 1, literal);
+        let bits: Vec<char> = packet_bits.chars().collect();
+
+        let packet = parse_bits(&bits);
+        let expected = (1u128 << 80) - 1;
+        match &packet {
+            Packet::Operator { sub_packets, .. } => match &sub_packets[0] {
+                Packet::Literal { num, .. } => assert_eq!(num.to_u128(), Some(expected)),
+                other => panic!("Expected a literal, got {:?}", other),
+            },
+            other => panic!("Expected an operator, got {:?}", other),
+        }
+
+        match evaluate::<u64>(&packet) {
+            Err(CommonError::Overflow(msg)) => {
+                assert_eq!(msg, "Literal of 80 bits at root/0 overflows u64")
+            }
+            other => panic!("Expected overflow, got {:?}", other),
+        }
+        assert_eq!(evaluate::<u128>(&packet).unwrap(), expected);
+        assert_eq!(
+            evaluate::<BigUint>(&packet).unwrap().to_string(),
+            expected.to_string()
+        );
+    }
+
+    #[test]
+    fn test_evaluate_arity() {
+        let literal = |num: u64| Packet::Literal {
+            version: 0,
+            num: BigUint::from(num),
+        };
+        let operator = |type_id, sub_packets| Packet::Operator {
+            version: 0,
+            type_id,
+            length_type_id: 1,
+            sub_packets,
+        };
+
+        let three_way = operator(
+            PacketType::LessThan,
+            vec![literal(1), literal(2), literal(3)],
+        );
+        assert!(matches!(
+            evaluate::<u64>(&three_way),
+            Err(CommonError::Evaluation(_))
+        ));
+
+        let nested_empty = operator(
+            PacketType::Sum,
+            vec![literal(1), operator(PacketType::Minimum, vec![])],
+        );
+        match evaluate::<u64>(&nested_empty) {
+            Err(CommonError::Evaluation(msg)) => assert!(msg.contains("root/1")),
+            other => panic!("Expected arity error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_d16() {
         let data = read_to_string("inputs/d16").unwrap();
@@ -339,7 +517,7 @@ mod tests {
 
         println!("Day 16 result #1: {}", version_sum);
 
-        let processed_result = process_packet(&packet);
+        let processed_result = process_packet(&packet).unwrap();
         println!("Day 16 result #2: {}", processed_result);
     }
 }
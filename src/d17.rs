@@ -1,32 +1,22 @@
+use crate::common::collections::interval::Interval;
 use std::collections::BTreeSet;
 
 #[derive(Debug)]
-struct Input {
-    x_min: i64,
-    x_max: i64,
-    y_min: i64,
-    y_max: i64,
+struct TargetArea {
+    x: Interval<i64>,
+    y: Interval<i64>,
 }
 
-fn parse_input<S: AsRef<str>>(data: S) -> Input {
+fn parse_input<S: AsRef<str>>(data: S) -> TargetArea {
     let mut iter = data.as_ref().trim()[13..].split(' ');
 
     let x_str = iter.next().unwrap();
-    let mut x_iter = x_str[2..x_str.len() - 1].split("..");
-    let x_min = x_iter.next().unwrap().parse().unwrap();
-    let x_max = x_iter.next().unwrap().parse().unwrap();
+    let x = x_str[2..x_str.len() - 1].parse().unwrap();
 
     let y_str = iter.next().unwrap();
-    let mut y_iter = y_str[2..].split("..");
-    let y_min = y_iter.next().unwrap().parse().unwrap();
-    let y_max = y_iter.next().unwrap().parse().unwrap();
-
-    Input {
-        x_min,
-        x_max,
-        y_min,
-        y_max,
-    }
+    let y = y_str[2..].parse().unwrap();
+
+    TargetArea { x, y }
 }
 
 // Since movement on the y axis is symmetrical, we can treat throwing up with v0_y the same as
@@ -36,7 +26,8 @@ fn parse_input<S: AsRef<str>>(data: S) -> Input {
 // Using Gauss's summation gives us
 // y_min <= v0_y * n - n * (n+1) <= y_max
 // Then do the same for x axis
-fn find_valid_v0_ys(y_min: i64, y_max: i64) -> Vec<(i64, i64)> {
+fn find_valid_v0_ys(y: &Interval<i64>) -> Vec<(i64, i64)> {
+    let y_min = y.start;
     let mut v0_y = y_min;
     let mut suitable_v0_ys = Vec::new();
 
@@ -48,7 +39,7 @@ fn find_valid_v0_ys(y_min: i64, y_max: i64) -> Vec<(i64, i64)> {
             n += 1;
 
             // Time taken is n downwards and 2*v0_y upwards (goes up and down)
-            if distance_y >= y_min && distance_y <= y_max {
+            if y.contains(distance_y) {
                 suitable_v0_ys.push((v0_y, n));
             }
         }
@@ -62,14 +53,14 @@ fn find_valid_v0_ys(y_min: i64, y_max: i64) -> Vec<(i64, i64)> {
 // Now that we have a list of suitable v0_y values, we do the same for x
 // For each v0_y candidate and time taken n, we try to find a valid v_0x
 // The only difference is that the v_0x is decreasing each step
-fn find_valid_v0_xs(n: i64, x_min: i64, x_max: i64) -> Vec<i64> {
+fn find_valid_v0_xs(n: i64, x: &Interval<i64>) -> Vec<i64> {
     let mut suitable_v0_xs = Vec::new();
     let mut v0_x = 1;
     let mut distance_x = 0;
-    while distance_x <= x_max {
+    while distance_x <= x.end {
         let lower = std::cmp::max(v0_x - n + 1, 0); // Velocity decrease over time
         distance_x = (v0_x + lower) * (v0_x - lower + 1) / 2; // Sum from lower to v_0x
-        if distance_x >= x_min && distance_x <= x_max {
+        if x.contains(distance_x) {
             suitable_v0_xs.push(v0_x);
         }
 
@@ -82,10 +73,10 @@ fn find_highest_point(v0_y: i64) -> i64 {
     v0_y * (v0_y + 1) / 2
 }
 
-fn find_combinations(ys: &[(i64, i64)], x_min: i64, x_max: i64) -> BTreeSet<(i64, i64)> {
+fn find_combinations(ys: &[(i64, i64)], x: &Interval<i64>) -> BTreeSet<(i64, i64)> {
     ys.iter()
         .map(|&(y, n)| {
-            find_valid_v0_xs(n, x_min, x_max)
+            find_valid_v0_xs(n, x)
                 .iter()
                 .map(|&x| (x, y))
                 .collect::<Vec<(i64, i64)>>()
@@ -101,21 +92,19 @@ mod tests {
 
     #[test]
     fn test() {
-        let x_min = 20;
-        let x_max = 30;
-        let y_min = -10;
-        let y_max = -5;
+        let x = Interval::new(20, 30);
+        let y = Interval::new(-10, -5);
 
-        let ys = find_valid_v0_ys(y_min, y_max);
+        let ys = find_valid_v0_ys(&y);
         let &(highest_y, n) = ys.iter().last().unwrap();
 
-        let xs = find_valid_v0_xs(n, x_min, x_max);
+        let xs = find_valid_v0_xs(n, &x);
         assert_eq!((xs[0], highest_y), (6, 9));
 
         let highest = find_highest_point(highest_y);
         assert_eq!(highest, 45);
 
-        let combinations = find_combinations(&ys, x_min, x_max);
+        let combinations = find_combinations(&ys, &x);
         assert_eq!(combinations.len(), 112);
     }
 
@@ -124,13 +113,13 @@ mod tests {
         let data = read_to_string("inputs/d17").unwrap();
         let input = parse_input(&data);
 
-        let ys = find_valid_v0_ys(input.y_min, input.y_max);
+        let ys = find_valid_v0_ys(&input.y);
         let &(v0_y, _) = ys.iter().last().unwrap();
 
         let highest_distance = find_highest_point(v0_y);
         println!("Day 17 result #1: {}", highest_distance);
 
-        let combinations = find_combinations(&ys, input.x_min, input.x_max);
+        let combinations = find_combinations(&ys, &input.x);
         println!("Day 17 result #2: {}", combinations.len());
     }
 }
@@ -1,49 +1,158 @@
+use crate::common::error::CommonError;
+use std::fmt::{Display, Formatter};
+use std::iter::Sum;
+use std::ops::Add;
+use std::str::FromStr;
+
 #[derive(PartialEq, Debug, Clone)]
 struct Digit {
     value: u32,
     depth: u8,
 }
 
-fn parse_line<S: AsRef<str>>(data: S) -> Vec<Digit> {
-    let mut cur_depth = 0;
-    let mut result = Vec::new();
-    let mut num = None;
-    for ch in data.as_ref().chars() {
-        match ch {
-            '[' => {
-                cur_depth += 1;
+/// A snailfish number. Internally it is kept as the flat list of regular numbers in left-to-right
+/// order, each tagged with its nesting depth, since explode and split only ever touch neighbours
+/// in that order.
+#[derive(PartialEq, Debug, Clone)]
+struct SnailfishNumber {
+    digits: Vec<Digit>,
+}
+
+impl SnailfishNumber {
+    fn magnitude(&self) -> u32 {
+        calculate_magnitude(&self.digits)
+    }
+
+    fn add_traced(&self, rhs: &SnailfishNumber) -> (SnailfishNumber, Vec<ReductionStep>) {
+        let mut digits = self.digits.clone();
+        let steps = add_line_to_number_traced(&rhs.digits, &mut digits);
+        (SnailfishNumber { digits }, steps)
+    }
+}
+
+impl FromStr for SnailfishNumber {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(SnailfishNumber {
+            digits: parse_line(s)?,
+        })
+    }
+}
+
+impl Display for SnailfishNumber {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        // Rebuilds the tree from depths: an element at the current depth is a regular number,
+        // anything deeper belongs to a pair that opens here.
+        fn write_element(
+            digits: &[Digit],
+            pos: &mut usize,
+            depth: u8,
+            f: &mut Formatter<'_>,
+        ) -> std::fmt::Result {
+            if digits[*pos].depth == depth {
+                *pos += 1;
+                write!(f, "{}", digits[*pos - 1].value)
+            } else {
+                write!(f, "[")?;
+                write_element(digits, pos, depth + 1, f)?;
+                write!(f, ",")?;
+                write_element(digits, pos, depth + 1, f)?;
+                write!(f, "]")
             }
-            ']' => {
-                if let Some(n) = num {
-                    result.push(Digit {
-                        value: n,
-                        depth: cur_depth,
-                    });
-                    num = None;
+        }
+
+        if self.digits.is_empty() {
+            return Ok(());
+        }
+        write_element(&self.digits, &mut 0, 0, f)
+    }
+}
+
+impl Add for &SnailfishNumber {
+    type Output = SnailfishNumber;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut digits = self.digits.clone();
+        add_line_to_number(&rhs.digits, &mut digits);
+        SnailfishNumber { digits }
+    }
+}
+
+impl Add for SnailfishNumber {
+    type Output = SnailfishNumber;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        add_line_to_number(&rhs.digits, &mut self.digits);
+        self
+    }
+}
+
+/// Panics on an empty iterator, since snailfish addition has no identity element.
+impl Sum for SnailfishNumber {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.reduce(|acc, number| acc + number)
+            .expect("Cannot sum an empty list of snailfish numbers")
+    }
+}
+
+// Recursive descent over `element := number | '[' element ',' element ']'`, where the outermost
+// element has to be a pair.
+fn parse_line<S: AsRef<str>>(data: S) -> Result<Vec<Digit>, CommonError> {
+    fn parse_element(
+        chars: &[char],
+        pos: &mut usize,
+        depth: u8,
+        result: &mut Vec<Digit>,
+    ) -> Result<(), CommonError> {
+        match chars.get(*pos) {
+            Some('[') => {
+                *pos += 1;
+                parse_element(chars, pos, depth + 1, result)?;
+                if chars.get(*pos) != Some(&',') {
+                    return Err(CommonError::Parse("Expected ',' inside snailfish pair."));
                 }
-                cur_depth -= 1;
-            }
-            digit if digit.is_digit(10) => {
-                let d = digit.to_digit(10).unwrap();
-                if let Some(n) = &mut num {
-                    *n = 10 * *n + d;
-                } else {
-                    num = Some(d);
+                *pos += 1;
+                parse_element(chars, pos, depth + 1, result)?;
+                if chars.get(*pos) != Some(&']') {
+                    return Err(CommonError::Parse("Expected ']' to close snailfish pair."));
                 }
+                *pos += 1;
+                Ok(())
             }
-            ',' => {
-                if let Some(n) = num {
-                    result.push(Digit {
-                        value: n,
-                        depth: cur_depth,
-                    });
-                    num = None;
+            Some(ch) if ch.is_ascii_digit() => {
+                let mut value: u32 = 0;
+                while let Some(d) = chars.get(*pos).and_then(|ch| ch.to_digit(10)) {
+                    value = value
+                        .checked_mul(10)
+                        .and_then(|v| v.checked_add(d))
+                        .ok_or(CommonError::Parse("Regular number too large."))?;
+                    *pos += 1;
                 }
+                result.push(Digit { value, depth });
+                Ok(())
             }
-            _ => {}
+            Some(_) => Err(CommonError::Parse(
+                "Unexpected character in snailfish number.",
+            )),
+            None => Err(CommonError::Parse("Unexpected end of snailfish number.")),
         }
     }
-    result
+
+    let chars: Vec<char> = data.as_ref().trim().chars().collect();
+    if chars.first() != Some(&'[') {
+        return Err(CommonError::Parse("Snailfish number has to be a pair."));
+    }
+
+    let mut pos = 0;
+    let mut result = Vec::new();
+    parse_element(&chars, &mut pos, 0, &mut result)?;
+    if pos != chars.len() {
+        return Err(CommonError::Parse(
+            "Trailing characters after snailfish number.",
+        ));
+    }
+    Ok(result)
 }
 
 fn add(pair: &[Digit], number: &mut Vec<Digit>) {
@@ -53,43 +162,49 @@ fn add(pair: &[Digit], number: &mut Vec<Digit>) {
     }
 }
 
+fn find_explode_position(number: &[Digit]) -> Option<usize> {
+    number.iter().position(|digit| digit.depth >= 5)
+}
+
+fn find_split_position(number: &[Digit]) -> Option<usize> {
+    number.iter().position(|digit| digit.value >= 10)
+}
+
 fn explode(number: &mut Vec<Digit>) -> bool {
-    for i in 0..number.len() {
-        if number[i].depth >= 5 {
-            if i != 0 {
-                number[i - 1].value += number[i].value;
-            }
-            if i + 2 < number.len() {
-                number[i + 2].value += number[i + 1].value;
-            }
-            number[i].depth -= 1;
-            number[i].value = 0;
-            number.remove(i + 1);
-            return true;
+    if let Some(i) = find_explode_position(number) {
+        if i != 0 {
+            number[i - 1].value += number[i].value;
         }
+        if i + 2 < number.len() {
+            number[i + 2].value += number[i + 1].value;
+        }
+        number[i].depth -= 1;
+        number[i].value = 0;
+        number.remove(i + 1);
+        true
+    } else {
+        false
     }
-    false
 }
 
 fn split(number: &mut Vec<Digit>) -> bool {
-    for i in 0..number.len() {
-        if number[i].value >= 10 {
-            let div = number[i].value as f64 / 2.0;
-            let left = div.floor() as u32;
-            let right = div.ceil() as u32;
-            number[i].depth += 1;
-            number[i].value = left;
-            number.insert(
-                i + 1,
-                Digit {
-                    value: right,
-                    depth: number[i].depth,
-                },
-            );
-            return true;
-        }
+    if let Some(i) = find_split_position(number) {
+        let div = number[i].value as f64 / 2.0;
+        let left = div.floor() as u32;
+        let right = div.ceil() as u32;
+        number[i].depth += 1;
+        number[i].value = left;
+        number.insert(
+            i + 1,
+            Digit {
+                value: right,
+                depth: number[i].depth,
+            },
+        );
+        true
+    } else {
+        false
     }
-    false
 }
 
 fn add_line_to_number(pair: &[Digit], number: &mut Vec<Digit>) {
@@ -105,10 +220,108 @@ fn add_line_to_number(pair: &[Digit], number: &mut Vec<Digit>) {
     }
 }
 
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+enum Side {
+    Left,
+    Right,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
+enum ReductionAction {
+    /// Explosion of the pair at the given path from the root.
+    Explode(Vec<Side>),
+    /// Split of the regular number at the given path from the root.
+    Split(Vec<Side>),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+struct ReductionStep {
+    action: ReductionAction,
+    result: SnailfishNumber,
+}
+
+// Tree path of the regular number at the given index of the flat representation.
+// Paths are visited in order: go left until the digit's depth, and afterwards move to the next
+// right sibling of the deepest left turn.
+fn digit_path(number: &[Digit], index: usize) -> Vec<Side> {
+    let mut path = Vec::new();
+    for (i, digit) in number.iter().enumerate() {
+        while path.len() < digit.depth as usize {
+            path.push(Side::Left);
+        }
+        if i == index {
+            break;
+        }
+        while path.last() == Some(&Side::Right) {
+            path.pop();
+        }
+        if let Some(last) = path.last_mut() {
+            *last = Side::Right;
+        }
+    }
+    path
+}
+
+/// Same as `add_line_to_number`, but records every explode and split along with the number it
+/// produced.
+fn add_line_to_number_traced(pair: &[Digit], number: &mut Vec<Digit>) -> Vec<ReductionStep> {
+    let mut steps = Vec::new();
+    add(pair, number);
+    loop {
+        let action = if let Some(i) = find_explode_position(number) {
+            let mut path = digit_path(number, i);
+            path.pop();
+            explode(number);
+            ReductionAction::Explode(path)
+        } else if let Some(i) = find_split_position(number) {
+            let path = digit_path(number, i);
+            split(number);
+            ReductionAction::Split(path)
+        } else {
+            break;
+        };
+
+        steps.push(ReductionStep {
+            action,
+            result: SnailfishNumber {
+                digits: number.clone(),
+            },
+        });
+    }
+    steps
+}
+
+/// Renders a reduction in the same layout as the worked example of the puzzle, e.g.
+/// ```text
+/// after addition: [[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]
+/// after explode:  [[[[0,7],4],[7,[[8,4],9]]],[1,1]]
+/// ```
+fn format_reduction_trace(
+    lhs: &SnailfishNumber,
+    rhs: &SnailfishNumber,
+    steps: &[ReductionStep],
+) -> String {
+    let mut unreduced = lhs.digits.clone();
+    add(&rhs.digits, &mut unreduced);
+
+    let mut lines = vec![format!(
+        "after addition: {}",
+        SnailfishNumber { digits: unreduced }
+    )];
+    for step in steps {
+        let name = match step.action {
+            ReductionAction::Explode(_) => "explode:",
+            ReductionAction::Split(_) => "split:",
+        };
+        lines.push(format!("after {:<10}{}", name, step.result));
+    }
+    lines.join("\n")
+}
+
 fn calculate_magnitude(number: &[Digit]) -> u32 {
     let mut temp = number.to_vec();
-    // We know that the max depth is 4
-    let mut depth = 4;
+    // The max depth is 4 after reduction, but unreduced numbers can go deeper
+    let mut depth = number.iter().map(|d| d.depth).max().unwrap_or(0);
     while depth > 0 {
         let mut processed = false;
         for i in 0..temp.len() - 1 {
@@ -128,10 +341,76 @@ fn calculate_magnitude(number: &[Digit]) -> u32 {
     temp[0].value
 }
 
+// Keeps the better of two candidates: higher magnitude first, then the smaller pair of indices.
+fn better_candidate(
+    a: Option<((usize, usize), u32)>,
+    b: Option<((usize, usize), u32)>,
+) -> Option<((usize, usize), u32)> {
+    match (a, b) {
+        (Some((pair_a, mag_a)), Some((pair_b, mag_b))) => {
+            if mag_b > mag_a || (mag_b == mag_a && pair_b < pair_a) {
+                b
+            } else {
+                a
+            }
+        }
+        (None, b) => b,
+        (a, None) => a,
+    }
+}
+
+/// Finds the ordered pair of distinct numbers whose sum has the largest magnitude, returning
+/// their indices and the magnitude. Rows of the pair matrix are dealt round-robin to
+/// `num_threads` scoped threads. Ties go to the lexicographically smallest pair, so the result
+/// does not depend on the number of threads.
+fn find_largest_pair_magnitude(
+    numbers: &[Vec<Digit>],
+    num_threads: usize,
+) -> Option<((usize, usize), u32)> {
+    let num_threads = num_threads.clamp(1, numbers.len().max(1));
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..num_threads)
+            .map(|thread_idx| {
+                scope.spawn(move || {
+                    let mut best = None;
+                    for i in (thread_idx..numbers.len()).step_by(num_threads) {
+                        for j in 0..numbers.len() {
+                            if i == j {
+                                continue;
+                            }
+                            let mut n = numbers[i].clone();
+                            add_line_to_number(&numbers[j], &mut n);
+                            best = better_candidate(best, Some(((i, j), calculate_magnitude(&n))));
+                        }
+                    }
+                    best
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .fold(None, better_candidate)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::common::file_io;
+    use crate::common::{file_io, parse};
+
+    const TEST_DATA: &str = r"[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]
+[[[5,[2,8]],4],[5,[[9,9],0]]]
+[6,[[[6,2],[5,6]],[[7,6],[4,7]]]]
+[[[6,[0,7]],[0,9]],[4,[9,[9,0]]]]
+[[[7,[6,4]],[3,[1,3]]],[[[5,5],1],9]]
+[[6,[[7,3],[3,2]]],[[[3,8],[5,7]],4]]
+[[[[5,4],[7,7]],8],[[8,3],8]]
+[[9,3],[[9,9],[6,[4,9]]]]
+[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]
+[[[[5,2],5],[8,[3,7]]],[[5,[7,5]],[4,4]]]";
 
     #[test]
     fn test_parse_chars() {
@@ -151,14 +430,14 @@ mod tests {
             Digit { value: 9, depth: 1 },
         ];
 
-        assert_eq!(parse_line(data1), expected1);
+        assert_eq!(parse_line(data1).unwrap(), expected1);
     }
 
     #[test]
     fn test_add() {
-        let to_add = parse_line("[3,5]");
+        let to_add = parse_line("[3,5]").unwrap();
 
-        let mut data1 = parse_line("[[1,9],[8,5]]");
+        let mut data1 = parse_line("[[1,9],[8,5]]").unwrap();
         let expected1 = vec![
             Digit { value: 1, depth: 3 },
             Digit { value: 9, depth: 3 },
@@ -171,7 +450,7 @@ mod tests {
         add(&to_add, &mut data1);
         assert_eq!(data1, expected1);
 
-        let mut data2 = parse_line("[9,[8,7]]");
+        let mut data2 = parse_line("[9,[8,7]]").unwrap();
         let expected2 = vec![
             Digit { value: 9, depth: 2 },
             Digit { value: 8, depth: 3 },
@@ -187,9 +466,9 @@ mod tests {
     #[test]
     fn test_explode() {
         fn helper((data_str, expected_str): (&str, &str)) {
-            let mut data = parse_line(data_str);
+            let mut data = parse_line(data_str).unwrap();
             explode(&mut data);
-            let expected = parse_line(expected_str);
+            let expected = parse_line(expected_str).unwrap();
             assert_eq!(data, expected);
         }
 
@@ -222,9 +501,9 @@ mod tests {
     #[test]
     fn test_split() {
         fn helper((data_str, expected_str): (&str, &str)) {
-            let mut data = parse_line(data_str);
+            let mut data = parse_line(data_str).unwrap();
             split(&mut data);
-            let expected = parse_line(expected_str);
+            let expected = parse_line(expected_str).unwrap();
             assert_eq!(data, expected);
         }
 
@@ -250,10 +529,10 @@ mod tests {
     #[test]
     fn test_add_line_to_number() {
         fn helper((data_str, to_add_str, expected_str): (&str, &str, &str)) {
-            let mut data = parse_line(data_str);
-            let to_add = parse_line(to_add_str);
+            let mut data = parse_line(data_str).unwrap();
+            let to_add = parse_line(to_add_str).unwrap();
             add_line_to_number(&to_add, &mut data);
-            let expected = parse_line(expected_str);
+            let expected = parse_line(expected_str).unwrap();
             assert_eq!(data, expected);
         }
 
@@ -311,7 +590,7 @@ mod tests {
     #[test]
     fn test_calculate_magnitude() {
         fn helper((data_str, expected): (&str, u32)) {
-            let data = parse_line(data_str);
+            let data = parse_line(data_str).unwrap();
             let result = calculate_magnitude(&data);
             assert_eq!(result, expected);
         }
@@ -335,31 +614,104 @@ mod tests {
         .for_each(helper);
     }
     #[test]
-    fn test_d18() {
-        let data = file_io::read_lines_as_strings("inputs/d18").unwrap();
-        let numbers: Vec<Vec<Digit>> = data.iter().map(parse_line).collect();
-        let mut result = numbers[0].clone();
+    fn test_snailfish_parse_display() {
+        for s in [
+            "[1,2]",
+            "[[1,2],3]",
+            "[9,[8,7]]",
+            "[[[[1,2],[13,4]],[[5,6],[7,8]]],9]",
+            "[[[[[9,8],1],2],3],4]",
+        ] {
+            let number: SnailfishNumber = s.parse().unwrap();
+            assert_eq!(number.to_string(), s);
+        }
 
-        for number in numbers.iter().skip(1) {
-            add_line_to_number(number, &mut result);
+        for malformed in [
+            "", "1", "[1,2", "[1,2]]", "[1;2]", "[1,2,3]", "[[1,2]]", "[,2]", "[1,x]",
+        ] {
+            assert!(
+                malformed.parse::<SnailfishNumber>().is_err(),
+                "{} should not parse",
+                malformed
+            );
         }
+    }
 
-        let magnitude = calculate_magnitude(&result);
+    #[test]
+    fn test_snailfish_add_sum() {
+        let a: SnailfishNumber = "[[[[4,3],4],4],[7,[[8,4],9]]]".parse().unwrap();
+        let b: SnailfishNumber = "[1,1]".parse().unwrap();
+        assert_eq!((&a + &b).to_string(), "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
 
-        println!("Day 18 result #1: {}", magnitude);
+        let numbers: Vec<SnailfishNumber> =
+            parse::transform_iter(TEST_DATA.lines(), |l| SnailfishNumber::from_str(l)).unwrap();
+        let sum: SnailfishNumber = numbers.into_iter().sum();
+        assert_eq!(
+            sum.to_string(),
+            "[[[[6,6],[7,6]],[[7,7],[7,0]]],[[[7,7],[7,7]],[[7,8],[9,9]]]]"
+        );
+        assert_eq!(sum.magnitude(), 4140);
+    }
 
-        let mut largest = u32::MIN;
-        for i in numbers.iter() {
-            for j in numbers.iter() {
-                let mut n = i.clone();
-                add_line_to_number(j, &mut n);
-                let magnitude = calculate_magnitude(&n);
-                if magnitude > largest {
-                    largest = magnitude;
-                }
-            }
+    #[test]
+    fn test_add_traced() {
+        let a: SnailfishNumber = "[[[[4,3],4],4],[7,[[8,4],9]]]".parse().unwrap();
+        let b: SnailfishNumber = "[1,1]".parse().unwrap();
+        let (sum, steps) = a.add_traced(&b);
+        assert_eq!(sum, &a + &b);
+
+        use Side::*;
+        let actions: Vec<ReductionAction> = steps.iter().map(|s| s.action.clone()).collect();
+        assert_eq!(
+            actions,
+            vec![
+                ReductionAction::Explode(vec![Left, Left, Left, Left]),
+                ReductionAction::Explode(vec![Left, Right, Right, Left]),
+                ReductionAction::Split(vec![Left, Right, Left]),
+                ReductionAction::Split(vec![Left, Right, Right, Right]),
+                ReductionAction::Explode(vec![Left, Right, Right, Right]),
+            ]
+        );
+
+        let trace = format_reduction_trace(&a, &b, &steps);
+        assert_eq!(
+            trace,
+            r"after addition: [[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]
+after explode:  [[[[0,7],4],[7,[[8,4],9]]],[1,1]]
+after explode:  [[[[0,7],4],[15,[0,13]]],[1,1]]
+after split:    [[[[0,7],4],[[7,8],[0,13]]],[1,1]]
+after split:    [[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]
+after explode:  [[[[0,7],4],[[7,8],[6,0]]],[8,1]]"
+        );
+    }
+
+    #[test]
+    fn test_find_largest_pair_magnitude() {
+        let numbers: Vec<Vec<Digit>> = TEST_DATA.lines().map(|l| parse_line(l).unwrap()).collect();
+        for num_threads in [1, 2, 3, 16] {
+            assert_eq!(
+                find_largest_pair_magnitude(&numbers, num_threads),
+                Some(((8, 0), 3993))
+            );
         }
 
+        // Identical numbers produce ties everywhere, the smallest pair has to win
+        let same = vec![parse_line("[1,1]").unwrap(); 4];
+        assert_eq!(find_largest_pair_magnitude(&same, 3), Some(((0, 1), 25)));
+        assert_eq!(find_largest_pair_magnitude(&same[..1], 3), None);
+    }
+
+    #[test]
+    fn test_d18() {
+        let numbers: Vec<SnailfishNumber> = file_io::read_lines_as_structs("inputs/d18").unwrap();
+        let magnitude = numbers.iter().cloned().sum::<SnailfishNumber>().magnitude();
+
+        println!("Day 18 result #1: {}", magnitude);
+
+        let digits: Vec<Vec<Digit>> = numbers.into_iter().map(|n| n.digits).collect();
+        let num_threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+        let (_, largest) = find_largest_pair_magnitude(&digits, num_threads).unwrap();
+
         println!("Day 18 result #2: {}", largest);
     }
 }
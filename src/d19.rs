@@ -1,5 +1,6 @@
-use crate::common::algorithms;
-use crate::common::collections::vec3d::{Vec3d, Vec3di};
+use crate::common::algorithms::search;
+use crate::common::collections::vec3d::{Rotation, Vec3d, Vec3di};
+use crate::common::error::CommonError;
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 // Distance between detected beacons are invariant w.r.t. scanner position
@@ -9,7 +10,16 @@ use std::collections::{BTreeMap, BTreeSet, HashMap};
 
 // This one was a mess. Should have approached differently and in a more principled manner.
 
-type DistanceMap = HashMap<Vec3di, Vec<(Vec3di, f64)>>;
+/// Squared distances between every pair of beacons seen by one scanner, indexed by distance.
+/// Squared integer distances are exact, so they can be hashed instead of compared with a
+/// tolerance.
+struct Fingerprints {
+    beacons: Vec<Vec3di>,
+    by_distance: HashMap<i64, Vec<(usize, usize)>>,
+}
+
+// Default number of beacons two scanners need to share to be considered overlapping
+const DEFAULT_MIN_OVERLAP: usize = 12;
 
 fn parse_chunks<S: AsRef<str>>(chunk: &[S]) -> Vec<Vec3di> {
     chunk
@@ -19,219 +29,394 @@ fn parse_chunks<S: AsRef<str>>(chunk: &[S]) -> Vec<Vec3di> {
         .collect()
 }
 
-fn compute_distances(coords: &[Vec3di]) -> DistanceMap {
-    let mut distances = DistanceMap::new();
-    for &b1 in coords.iter() {
-        distances.insert(b1, Vec::new());
-        for &b2 in coords.iter() {
-            // if b1 == b2 { continue; }
-            let dist = b1.distance_l2(&b2);
-            distances.get_mut(&b1).unwrap().push((b2, dist));
+fn compute_fingerprints(coords: &[Vec3di]) -> Fingerprints {
+    let mut by_distance: HashMap<i64, Vec<(usize, usize)>> = HashMap::new();
+    for i in 0..coords.len() {
+        for j in (i + 1)..coords.len() {
+            by_distance
+                .entry(coords[i].distance_l2_squared(&coords[j]))
+                .or_default()
+                .push((i, j));
         }
     }
-    distances
+    Fingerprints {
+        beacons: coords.to_vec(),
+        by_distance,
+    }
 }
 
+/// Pairs up beacons of two scanners that share at least `min_overlap - 1` distances to other
+/// beacons, i.e. that look alike from within an overlap of `min_overlap` beacons.
+/// Every pair of beacon pairs with the same distance votes for the four possible
+/// correspondences of their endpoints, which only adds up for the right ones.
 fn find_common_beacons(
-    scanner1_inter_distances: &DistanceMap,
-    scanner2_inter_distances: &DistanceMap,
+    scanner1_fingerprints: &Fingerprints,
+    scanner2_fingerprints: &Fingerprints,
+    min_overlap: usize,
 ) -> Vec<(Vec3di, Vec3di)> {
-    fn find_num_common_in_distance_vector(vec1: &[(Vec3di, f64)], vec2: &[(Vec3di, f64)]) -> usize {
-        let mut common = 0;
+    // An overlap of n beacons shares at least n * (n - 1) / 2 distances, skip early otherwise
+    let shared_distances: usize = scanner1_fingerprints
+        .by_distance
+        .iter()
+        .filter_map(|(dist, pairs1)| {
+            scanner2_fingerprints
+                .by_distance
+                .get(dist)
+                .map(|pairs2| pairs1.len().min(pairs2.len()))
+        })
+        .sum();
+    if shared_distances < min_overlap * min_overlap.saturating_sub(1) / 2 {
+        return Vec::new();
+    }
 
-        for (_, dist1) in vec1 {
-            for (_, dist2) in vec2 {
-                if (dist1 - dist2).abs() < 0.01 {
-                    common += 1;
+    let mut votes: HashMap<(usize, usize), usize> = HashMap::new();
+    for (dist, pairs1) in &scanner1_fingerprints.by_distance {
+        if let Some(pairs2) = scanner2_fingerprints.by_distance.get(dist) {
+            for &(a1, a2) in pairs1 {
+                for &(b1, b2) in pairs2 {
+                    for correspondence in [(a1, b1), (a1, b2), (a2, b1), (a2, b2)] {
+                        *votes.entry(correspondence).or_insert(0) += 1;
+                    }
                 }
             }
         }
-
-        common
     }
 
-    let mut result = Vec::new();
-    for (coord1, dists1) in scanner1_inter_distances {
-        for (coord2, dists2) in scanner2_inter_distances {
-            let common = find_num_common_in_distance_vector(dists1, dists2);
-            if common >= 12 {
-                result.push((*coord1, *coord2));
-            }
+    let mut result: Vec<(Vec3di, Vec3di)> = votes
+        .into_iter()
+        .filter(|&(_, count)| count + 1 >= min_overlap)
+        .map(|((i, j), _)| {
+            (
+                scanner1_fingerprints.beacons[i],
+                scanner2_fingerprints.beacons[j],
+            )
+        })
+        .collect();
+    result.sort();
+    result
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Alignment {
+    scanner2_seen_by_scanner1: Vec3di,
+    rotation_1_to_2: Rotation,
+    scanner1_seen_by_scanner2: Vec3di,
+    rotation_2_to_1: Rotation,
+    /// Number of common beacon pairs that agree with the alignment
+    support: usize,
+}
+
+/// How well two scanners were matched against each other.
+#[derive(Debug, PartialEq, Clone)]
+struct ScannerMatch {
+    scanners: (usize, usize),
+    /// Beacon pairs whose distance fingerprints agree
+    candidates: usize,
+    /// Candidates that line up under the chosen rotation and offset, zero if none was found
+    aligned: usize,
+}
+
+impl ScannerMatch {
+    fn confidence(&self) -> f64 {
+        if self.candidates == 0 {
+            0.0
+        } else {
+            self.aligned as f64 / self.candidates as f64
         }
     }
-
-    result
 }
 
-type TransformationArray = [(u8, i8); 3];
+/// Finds the rotation and offset that map scanner 2 coordinates onto scanner 1 coordinates.
+/// Every proper rotation is tried, and each common beacon pair votes for the scanner offset it
+/// implies under that rotation. The winning rotation has to be unambiguous and be supported by
+/// at least `min_overlap` pairs, so a few mismatched pairs can't derail the alignment.
 fn find_scanner_offsets_from_common_beacons(
     common_beacons: &[(Vec3di, Vec3di)],
-) -> (Vec3di, TransformationArray, Vec3di, TransformationArray) {
-    let (beacon0_scanner1, beacon0_scanner2) = common_beacons[0];
-    let (beacon1_scanner1, beacon1_scanner2) = common_beacons[1];
-
-    // Walking from beacon0 to beacon1 should be the same for both scanners, with axes and signs flipped.
-    let scanner1_delta = beacon1_scanner1 - beacon0_scanner1;
-    let scanner2_delta = beacon1_scanner2 - beacon0_scanner2;
-
-    // We find the axes with same absolute difference, extract the map (e.g. x -> -z, y -> -y) from there
-    let mut scanner2_seen_by_scanner1 = Vec3di::default();
-    let mut scanner1_seen_by_scanner2 = Vec3di::default();
-    let mut mapping_1_to_2 = [(0u8, 0i8); 3];
-    let mut mapping_2_to_1 = [(0u8, 0i8); 3];
-
-    for scanner1_coord in 0..3 {
-        for scanner2_coord in 0..3 {
-            if scanner1_delta[scanner1_coord].abs() == scanner2_delta[scanner2_coord].abs() {
-                let sign = scanner1_delta[scanner1_coord].signum()
-                    * scanner2_delta[scanner2_coord].signum();
-                scanner2_seen_by_scanner1[scanner1_coord] =
-                    beacon0_scanner1[scanner1_coord] - beacon0_scanner2[scanner2_coord] * sign;
-                scanner1_seen_by_scanner2[scanner2_coord] =
-                    beacon0_scanner2[scanner2_coord] - beacon0_scanner1[scanner1_coord] * sign;
-                mapping_1_to_2[scanner1_coord as usize] = (scanner2_coord as u8, sign as i8);
-                mapping_2_to_1[scanner2_coord as usize] = (scanner1_coord as u8, sign as i8);
+    min_overlap: usize,
+) -> Option<Alignment> {
+    let mut best: Option<(usize, Rotation, Vec3di)> = None;
+    let mut ambiguous = false;
+
+    for rotation in Rotation::all() {
+        let mut votes: HashMap<Vec3di, usize> = HashMap::new();
+        for (beacon_scanner1, beacon_scanner2) in common_beacons {
+            *votes
+                .entry(beacon_scanner1 - &rotation.apply(beacon_scanner2))
+                .or_insert(0) += 1;
+        }
+
+        if let Some((&offset, &count)) = votes.iter().max_by_key(|&(_, count)| *count) {
+            match best {
+                Some((best_count, ..)) if count < best_count => {}
+                Some((best_count, ..)) if count == best_count => ambiguous = true,
+                _ => {
+                    best = Some((count, rotation, offset));
+                    ambiguous = false;
+                }
             }
         }
     }
 
-    if mapping_1_to_2.iter().any(|&(_, sign)| sign == 0) {
-        panic!("Unassigned mapping!");
+    let (count, rotation_2_to_1, scanner2_seen_by_scanner1) = best?;
+    if ambiguous || count < min_overlap {
+        return None;
     }
 
-    (
+    let rotation_1_to_2 = rotation_2_to_1.inverse();
+    let scanner1_seen_by_scanner2 = -rotation_1_to_2.apply(&scanner2_seen_by_scanner1);
+
+    Some(Alignment {
         scanner2_seen_by_scanner1,
-        mapping_1_to_2,
+        rotation_1_to_2,
         scanner1_seen_by_scanner2,
-        mapping_2_to_1,
-    )
-}
-
-// Transforms a given vector to another reference
-fn transform_vec(v: &Vec3di, mapping: &[(u8, i8); 3]) -> Vec3di {
-    let mut result = Vec3di::default();
-
-    for (i, &(pos, sign)) in mapping.iter().enumerate() {
-        result[pos as usize] = v[i] * sign as i64;
-    }
-    result
-}
-
-fn find_path(from: usize, transformations: &BTreeMap<(usize, usize), [(u8, i8); 3]>) -> Vec<usize> {
-    let check_fn = |state: &Vec<usize>| state[state.len() - 1] == 0;
-    let expand_fn = |state: &Vec<usize>| {
-        let last = state[state.len() - 1];
-        transformations
-            .keys()
-            .filter_map(|(f, t)| {
-                if !state.contains(t) && *f == last {
-                    let mut new_state = state.clone();
-                    new_state.push(*t);
-                    Some(new_state)
-                } else {
-                    None
-                }
-            })
-            .collect()
-    };
-    algorithms::search::dfs(vec![from], check_fn, expand_fn).unwrap()
+        rotation_2_to_1,
+        support: count,
+    })
 }
 
 type ScannerOffsetMap = BTreeMap<(usize, usize), Vec3di>;
-type TransformationMap = BTreeMap<(usize, usize), TransformationArray>;
+type TransformationMap = BTreeMap<(usize, usize), Rotation>;
+/// Position and rotation into scanner 0's frame, indexed by scanner
+type ScannerPoses = Vec<(Vec3di, Rotation)>;
 
+/// Aligns every pair of overlapping scanners directly against each other.
 fn find_all_scanner_offsets(
-    beacon_interdistances_per_scanner: &[DistanceMap],
-) -> (ScannerOffsetMap, TransformationMap, Vec<Vec<usize>>) {
-    let mut offsets: BTreeMap<(usize, usize), Vec3di> = BTreeMap::new();
-    let mut transformations: BTreeMap<(usize, usize), [(u8, i8); 3]> = BTreeMap::new();
-
-    for i in 0..beacon_interdistances_per_scanner.len() - 1 {
-        for j in (i + 1)..beacon_interdistances_per_scanner.len() {
+    fingerprints_per_scanner: &[Fingerprints],
+    min_overlap: usize,
+) -> (ScannerOffsetMap, TransformationMap, Vec<ScannerMatch>) {
+    let mut offsets = ScannerOffsetMap::new();
+    let mut transformations = TransformationMap::new();
+    let mut matches = Vec::new();
+
+    for i in 0..fingerprints_per_scanner.len() {
+        for j in (i + 1)..fingerprints_per_scanner.len() {
             let common = find_common_beacons(
-                &beacon_interdistances_per_scanner[i],
-                &beacon_interdistances_per_scanner[j],
+                &fingerprints_per_scanner[i],
+                &fingerprints_per_scanner[j],
+                min_overlap,
             );
-            if !common.is_empty() {
-                let (j_by_i, mapping_ij, i_by_j, mapping_ji) =
-                    find_scanner_offsets_from_common_beacons(&common);
-                offsets.insert((i, j), j_by_i);
-                offsets.insert((j, i), i_by_j);
-                transformations.insert((i, j), mapping_ij);
-                transformations.insert((j, i), mapping_ji);
+            if common.is_empty() {
+                continue;
+            }
+
+            let alignment = find_scanner_offsets_from_common_beacons(&common, min_overlap);
+            matches.push(ScannerMatch {
+                scanners: (i, j),
+                candidates: common.len(),
+                aligned: alignment.as_ref().map_or(0, |a| a.support),
+            });
+            if let Some(alignment) = alignment {
+                offsets.insert((i, j), alignment.scanner2_seen_by_scanner1);
+                offsets.insert((j, i), alignment.scanner1_seen_by_scanner2);
+                transformations.insert((i, j), alignment.rotation_1_to_2);
+                transformations.insert((j, i), alignment.rotation_2_to_1);
             }
         }
     }
 
-    // Fill the holes by finding the offset of i-j by using paths i-m-j
-    let paths: Vec<Vec<usize>> = (1..beacon_interdistances_per_scanner.len())
-        .map(|from| find_path(from, &transformations))
-        .collect();
+    (offsets, transformations, matches)
+}
 
-    for path in &paths {
-        let edges = path.windows(2);
-        let j = path[0];
-        for edge in edges {
-            let m = edge[0];
-            let i = edge[1];
-            if offsets.contains_key(&(i, j)) {
-                // We already did this
-                continue;
-            }
-            let im = offsets.get(&(i, m)).unwrap();
-            let mj = offsets.get(&(m, j)).unwrap();
-            let mi_map = transformations.get(&(m, i)).unwrap();
+/// Places every scanner in scanner 0's frame by walking a BFS spanning tree of the overlap
+/// graph, so each absolute pose is composed exactly once from its parent's.
+fn find_scanner_poses(
+    num_scanners: usize,
+    offsets: &ScannerOffsetMap,
+    transformations: &TransformationMap,
+) -> Result<ScannerPoses, CommonError> {
+    let expand_fn = |&scanner: &usize| {
+        transformations
+            .range((scanner, 0)..(scanner + 1, 0))
+            .map(|(&(_, neighbour), _)| neighbour)
+            .collect()
+    };
 
-            // go from (j->m) to (k->i) by mapping using k->i transformation
-            let offset = im + &transform_vec(mj, mi_map);
-            offsets.insert((i, j), offset);
-        }
+    let mut poses: Vec<Option<(Vec3di, Rotation)>> = vec![None; num_scanners];
+    for (scanner, parent) in search::bfs(0, expand_fn) {
+        poses[scanner] = Some(match parent {
+            None => (Vec3di::default(), Rotation::identity()),
+            Some(parent) => {
+                // BFS visits parents first, so their pose is already known
+                let (parent_position, parent_rotation) = poses[parent].unwrap();
+                let position =
+                    parent_position + parent_rotation.apply(&offsets[&(parent, scanner)]);
+                let rotation = parent_rotation.compose(&transformations[&(scanner, parent)]);
+                (position, rotation)
+            }
+        });
     }
 
-    (offsets, transformations, paths)
+    let disconnected: Vec<usize> = (0..num_scanners).filter(|&i| poses[i].is_none()).collect();
+    if !disconnected.is_empty() {
+        return Err(CommonError::Disconnected(disconnected));
+    }
+    Ok(poses.into_iter().flatten().collect())
 }
 
 fn find_unique_beacons(
     beacon_positions_per_scanner: &[Vec<Vec3di>],
-    offsets: &BTreeMap<(usize, usize), Vec3di>,
-    transformations: &BTreeMap<(usize, usize), [(u8, i8); 3]>,
-    paths: &[Vec<usize>],
+    poses: &[(Vec3di, Rotation)],
 ) -> BTreeSet<Vec3di> {
-    let mut positions: BTreeSet<Vec3di> = BTreeSet::new();
-    positions.extend(&beacon_positions_per_scanner[0]);
-
-    for scanner in 1..beacon_positions_per_scanner.len() {
-        let path = &paths[scanner - 1];
-        for beacon_seen_by_scanner in &beacon_positions_per_scanner[scanner] {
-            let mut cur = *beacon_seen_by_scanner;
-            let edges = path.windows(2);
-            for edge in edges {
-                let from = edge[0];
-                let to = edge[1];
-
-                let scanner_offset = offsets.get(&(to, from)).unwrap();
-                let transformation = transformations.get(&(from, to)).unwrap();
-
-                cur = &transform_vec(&cur, transformation) + scanner_offset;
-            }
-            positions.insert(cur);
+    beacon_positions_per_scanner
+        .iter()
+        .zip(poses)
+        .flat_map(|(beacons, (position, rotation))| {
+            beacons
+                .iter()
+                .map(move |beacon| position + &rotation.apply(beacon))
+        })
+        .collect()
+}
+
+fn compute_manhattan(poses: &[(Vec3di, Rotation)]) -> u64 {
+    let mut highest = 0;
+    for (i, (position_i, _)) in poses.iter().enumerate() {
+        for (position_j, _) in poses.iter().skip(i + 1) {
+            highest = highest.max(position_i.manhattan_distance(position_j) as u64);
         }
     }
+    highest
+}
 
-    positions
+/// The assembled map in scanner 0 coordinates.
+#[derive(Debug)]
+struct ScannerMap {
+    beacons: BTreeSet<Vec3di>,
+    /// Position of every scanner, and the rotation from its own frame into scanner 0's frame
+    scanners: Vec<(Vec3di, Rotation)>,
 }
 
-fn compute_manhattan(offsets: &BTreeMap<(usize, usize), Vec3di>, len: usize) -> u64 {
-    let mut highest = f64::MIN;
-    for i in 1..len - 1 {
-        for j in i + 1..len {
-            let dist = (offsets.get(&(0, i)).unwrap() - offsets.get(&(0, j)).unwrap()).norm_l1();
-            if dist > highest {
-                highest = dist;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PointCloudFormat {
+    /// ASCII PLY with coloured vertices, scanner axes are drawn as edges
+    Ply,
+    /// Wavefront OBJ with beacons and scanners as separate objects, scanner axes as lines
+    Obj,
+    /// XYZ with a `B`/`S` element column, which has no room for orientations
+    Xyz,
+    /// One row per point, scanners carry their row-major rotation matrix
+    Csv,
+}
+
+// Length of the axis markers drawn for each scanner
+const AXIS_LENGTH: i64 = 100;
+
+fn build_scanner_map(
+    beacon_positions_per_scanner: &[Vec<Vec3di>],
+    poses: &[(Vec3di, Rotation)],
+) -> ScannerMap {
+    ScannerMap {
+        beacons: find_unique_beacons(beacon_positions_per_scanner, poses),
+        scanners: poses.to_vec(),
+    }
+}
+
+impl ScannerMap {
+    // Scanner position followed by the tips of its x, y and z axes
+    fn scanner_axes(position: &Vec3di, rotation: &Rotation) -> [Vec3di; 4] {
+        let tip = |x, y, z| position + &rotation.apply(&Vec3di::new(x, y, z));
+        [
+            *position,
+            tip(AXIS_LENGTH, 0, 0),
+            tip(0, AXIS_LENGTH, 0),
+            tip(0, 0, AXIS_LENGTH),
+        ]
+    }
+
+    fn export(&self, format: PointCloudFormat) -> String {
+        let mut lines: Vec<String> = Vec::new();
+        match format {
+            PointCloudFormat::Ply => {
+                lines.push("ply".to_string());
+                lines.push("format ascii 1.0".to_string());
+                lines.push(format!(
+                    "element vertex {}",
+                    self.beacons.len() + 4 * self.scanners.len()
+                ));
+                for property in ["int x", "int y", "int z"] {
+                    lines.push(format!("property {}", property));
+                }
+                for property in ["red", "green", "blue"] {
+                    lines.push(format!("property uchar {}", property));
+                }
+                lines.push(format!("element edge {}", 3 * self.scanners.len()));
+                lines.push("property int vertex1".to_string());
+                lines.push("property int vertex2".to_string());
+                lines.push("end_header".to_string());
+
+                for b in &self.beacons {
+                    lines.push(format!("{} {} {} 255 255 255", b.x, b.y, b.z));
+                }
+                for (position, rotation) in &self.scanners {
+                    let axes = Self::scanner_axes(position, rotation);
+                    let colours = ["255 255 0", "255 0 0", "0 255 0", "0 0 255"];
+                    for (v, colour) in axes.iter().zip(colours) {
+                        lines.push(format!("{} {} {} {}", v.x, v.y, v.z, colour));
+                    }
+                }
+                for scanner in 0..self.scanners.len() {
+                    let origin = self.beacons.len() + 4 * scanner;
+                    for axis in 1..=3 {
+                        lines.push(format!("{} {}", origin, origin + axis));
+                    }
+                }
+            }
+            PointCloudFormat::Obj => {
+                lines.push("o beacons".to_string());
+                for b in &self.beacons {
+                    lines.push(format!("v {} {} {}", b.x, b.y, b.z));
+                }
+                for (i, (position, rotation)) in self.scanners.iter().enumerate() {
+                    lines.push(format!("o scanner_{}", i));
+                    for v in Self::scanner_axes(position, rotation) {
+                        lines.push(format!("v {} {} {}", v.x, v.y, v.z));
+                    }
+                    // OBJ indices are 1-based
+                    let origin = self.beacons.len() + 4 * i + 1;
+                    for axis in 1..=3 {
+                        lines.push(format!("l {} {}", origin, origin + axis));
+                    }
+                }
+            }
+            PointCloudFormat::Xyz => {
+                lines.push((self.beacons.len() + self.scanners.len()).to_string());
+                lines.push("Beacons (B) and scanners (S) in scanner 0 coordinates".to_string());
+                for b in &self.beacons {
+                    lines.push(format!("B {} {} {}", b.x, b.y, b.z));
+                }
+                for (p, _) in &self.scanners {
+                    lines.push(format!("S {} {} {}", p.x, p.y, p.z));
+                }
+            }
+            PointCloudFormat::Csv => {
+                lines.push("kind,index,x,y,z,r00,r01,r02,r10,r11,r12,r20,r21,r22".to_string());
+                for (i, b) in self.beacons.iter().enumerate() {
+                    lines.push(format!("beacon,{},{},{},{},,,,,,,,,", i, b.x, b.y, b.z));
+                }
+                for (i, (p, rotation)) in self.scanners.iter().enumerate() {
+                    let matrix: Vec<String> = rotation
+                        .matrix()
+                        .rows
+                        .iter()
+                        .flatten()
+                        .map(|e| e.to_string())
+                        .collect();
+                    lines.push(format!(
+                        "scanner,{},{},{},{},{}",
+                        i,
+                        p.x,
+                        p.y,
+                        p.z,
+                        matrix.join(",")
+                    ));
+                }
             }
         }
+
+        let mut result = lines.join("\n");
+        result.push('\n');
+        result
     }
-    highest as u64
 }
 
 #[cfg(test)]
@@ -265,13 +450,14 @@ mod tests {
         let data = file_io::read_lines_as_string_groups("inputs/d19_test").unwrap();
         let beacon_positions_per_scanner: Vec<Vec<Vec3di>> =
             data.iter().map(|s| parse_chunks(s)).collect();
-        let scanner0_interdistances: DistanceMap =
-            compute_distances(&beacon_positions_per_scanner[0]);
-        let scanner1_interdistances: DistanceMap =
-            compute_distances(&beacon_positions_per_scanner[1]);
+        let scanner0_fingerprints = compute_fingerprints(&beacon_positions_per_scanner[0]);
+        let scanner1_fingerprints = compute_fingerprints(&beacon_positions_per_scanner[1]);
 
-        let common_beacons =
-            find_common_beacons(&scanner0_interdistances, &scanner1_interdistances);
+        let common_beacons = find_common_beacons(
+            &scanner0_fingerprints,
+            &scanner1_fingerprints,
+            DEFAULT_MIN_OVERLAP,
+        );
 
         let expected_0: BTreeSet<Vec3di> = r"-618,-824,-621
 -537,-823,-458
@@ -309,17 +495,21 @@ mod tests {
             common_beacons.into_iter().unzip();
         assert_eq!(s0_beacons, expected_0);
         assert_eq!(s1_beacons, expected_1);
+
+        // The two scanners share exactly 12 beacons
+        assert!(find_common_beacons(&scanner0_fingerprints, &scanner1_fingerprints, 13).is_empty());
     }
 
     #[test]
     fn test_transform_vec() {
         let v1_to_v2 = Vec3di::new(3, -1, 2);
         let v2_to_v1 = Vec3di::new(1, 2, 3);
-        let ref_1to2 = [(2, -1), (0, 1), (1, -1)]; // x -> -z, y -> x, z -> -y
-        let ref_2to1 = [(1, 1), (2, -1), (0, -1)];
+        // x -> -z, y -> x, z -> -y
+        let ref_1to2 = Rotation::from_matrix([[0, 1, 0], [0, 0, -1], [-1, 0, 0]]).unwrap();
+        let ref_2to1 = ref_1to2.inverse();
 
-        assert_eq!(transform_vec(&v1_to_v2, &ref_1to2), -v2_to_v1);
-        assert_eq!(transform_vec(&v2_to_v1, &ref_2to1), -v1_to_v2);
+        assert_eq!(ref_1to2.apply(&v1_to_v2), -v2_to_v1);
+        assert_eq!(ref_2to1.apply(&v2_to_v1), -v1_to_v2);
 
         let beacon = Vec3di::new(3, 4, 5);
         let scanner1 = Vec3di::new(1, 1, 1);
@@ -328,56 +518,163 @@ mod tests {
         let scanner2_from_1 = scanner2 - scanner1;
         let beacon_from_1 = beacon - scanner1;
 
-        let ref_1_to_2 = [(1, -1), (0, -1), (2, -1)];
-        let ref_2_to_1 = [(1, -1), (0, -1), (2, -1)];
+        let ref_1_to_2 = Rotation::from_matrix([[0, -1, 0], [-1, 0, 0], [0, 0, -1]]).unwrap();
+        let ref_2_to_1 = ref_1_to_2.inverse();
         let beacon_from_2 = Vec3di::new(1, 2, 0);
 
         assert_eq!(
-            transform_vec(&(beacon_from_1 - scanner2_from_1), &ref_1_to_2),
+            ref_1_to_2.apply(&(beacon_from_1 - scanner2_from_1)),
             beacon_from_2
         );
         assert_eq!(
-            scanner2_from_1 + transform_vec(&beacon_from_2, &ref_2_to_1),
+            scanner2_from_1 + ref_2_to_1.apply(&beacon_from_2),
             beacon_from_1
         );
     }
 
+    #[test]
+    fn test_find_scanner_offsets_symmetric_deltas() {
+        // Beacon deltas with equal component magnitudes used to leave axes unassigned
+        let rotation = Rotation::from_matrix([[0, 0, 1], [1, 0, 0], [0, 1, 0]]).unwrap();
+        let scanner2_position = Vec3di::new(100, -50, 7);
+        let mut beacons_scanner1 = vec![Vec3di::new(0, 0, 0), Vec3di::new(5, 5, -5)];
+        beacons_scanner1.extend((2..12).map(|i| Vec3di::new(i * 7 % 13 - 6, i * i % 17, i * 3)));
+        let common: Vec<(Vec3di, Vec3di)> = beacons_scanner1
+            .iter()
+            .map(|b| (*b, rotation.inverse().apply(&(b - &scanner2_position))))
+            .collect();
+
+        let alignment =
+            find_scanner_offsets_from_common_beacons(&common, DEFAULT_MIN_OVERLAP).unwrap();
+        assert_eq!(alignment.scanner2_seen_by_scanner1, scanner2_position);
+        assert_eq!(alignment.rotation_2_to_1, rotation);
+        assert_eq!(alignment.rotation_1_to_2, rotation.inverse());
+        assert_eq!(
+            alignment.scanner1_seen_by_scanner2,
+            -rotation.inverse().apply(&scanner2_position)
+        );
+        assert_eq!(alignment.support, 12);
+
+        // Mirrored beacons can't be explained by any proper rotation
+        let mirrored: Vec<(Vec3di, Vec3di)> = common
+            .iter()
+            .map(|&(b1, b2)| (b1, Vec3di::new(-b2.x, b2.y, b2.z)))
+            .collect();
+        assert!(find_scanner_offsets_from_common_beacons(&mirrored, DEFAULT_MIN_OVERLAP).is_none());
+    }
+
     #[test]
     fn test_offsets_and_unique() {
         let data = file_io::read_lines_as_string_groups("inputs/d19_test").unwrap();
         let beacon_positions_per_scanner: Vec<Vec<Vec3di>> =
             data.iter().map(|s| parse_chunks(s)).collect();
-        let beacon_interdistances_per_scanner: Vec<DistanceMap> = beacon_positions_per_scanner
+        let fingerprints_per_scanner: Vec<Fingerprints> = beacon_positions_per_scanner
             .iter()
-            .map(|coords| compute_distances(coords))
+            .map(|coords| compute_fingerprints(coords))
             .collect();
 
-        let (offsets, transformations, paths) =
-            find_all_scanner_offsets(&beacon_interdistances_per_scanner);
-
+        let (offsets, transformations, matches) =
+            find_all_scanner_offsets(&fingerprints_per_scanner, DEFAULT_MIN_OVERLAP);
         assert_eq!(offsets.get(&(0, 1)).unwrap(), &Vec3di::new(68, -1246, -43));
 
-        assert_eq!(
-            offsets.get(&(0, 2)).unwrap(),
-            &Vec3di::new(1105, -1205, 1229)
-        );
+        let poses = find_scanner_poses(data.len(), &offsets, &transformations).unwrap();
+        assert_eq!(poses[0], (Vec3di::default(), Rotation::identity()));
+        assert_eq!(poses[1].0, Vec3di::new(68, -1246, -43));
+        assert_eq!(poses[2].0, Vec3di::new(1105, -1205, 1229));
+        assert_eq!(poses[3].0, Vec3di::new(-92, -2380, -20));
+        assert_eq!(poses[4].0, Vec3di::new(-20, -1133, 1061));
+
+        let unique_beacons = find_unique_beacons(&beacon_positions_per_scanner, &poses);
+        assert_eq!(unique_beacons.len(), 79);
 
-        assert_eq!(offsets.get(&(0, 3)).unwrap(), &Vec3di::new(-92, -2380, -20));
+        assert_eq!(compute_manhattan(&poses), 3621);
 
-        assert_eq!(
-            offsets.get(&(0, 4)).unwrap(),
-            &Vec3di::new(-20, -1133, 1061)
-        );
+        let matched_pairs: Vec<(usize, usize)> = matches.iter().map(|m| m.scanners).collect();
+        assert_eq!(matched_pairs, vec![(0, 1), (1, 3), (1, 4), (2, 4)]);
+        for scanner_match in &matches {
+            assert!(scanner_match.aligned >= DEFAULT_MIN_OVERLAP);
+            assert_eq!(scanner_match.confidence(), 1.0);
+        }
+    }
 
-        let unique_beacons = find_unique_beacons(
-            &beacon_positions_per_scanner,
-            &offsets,
-            &transformations,
-            &paths,
-        );
-        assert_eq!(unique_beacons.len(), 79);
+    #[test]
+    fn test_find_all_scanner_offsets_empty() {
+        let (offsets, transformations, matches) =
+            find_all_scanner_offsets(&[], DEFAULT_MIN_OVERLAP);
+        assert!(offsets.is_empty() && transformations.is_empty() && matches.is_empty());
+    }
+
+    #[test]
+    fn test_find_scanner_poses_disconnected() {
+        let data = file_io::read_lines_as_string_groups("inputs/d19_test").unwrap();
+        let beacon_positions_per_scanner: Vec<Vec<Vec3di>> =
+            data.iter().map(|s| parse_chunks(s)).collect();
+        let fingerprints_per_scanner: Vec<Fingerprints> = beacon_positions_per_scanner
+            .iter()
+            .map(|coords| compute_fingerprints(coords))
+            .collect();
+        let (mut offsets, mut transformations, _) =
+            find_all_scanner_offsets(&fingerprints_per_scanner, DEFAULT_MIN_OVERLAP);
 
-        assert_eq!(compute_manhattan(&offsets, data.len()), 3621);
+        // Scanner 2 only overlaps with scanner 4
+        for key in [(2, 4), (4, 2)] {
+            offsets.remove(&key);
+            transformations.remove(&key);
+        }
+        match find_scanner_poses(data.len(), &offsets, &transformations) {
+            Err(CommonError::Disconnected(scanners)) => assert_eq!(scanners, vec![2]),
+            other => panic!("Expected disconnected scanners, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_export_scanner_map() {
+        let data = file_io::read_lines_as_string_groups("inputs/d19_test").unwrap();
+        let beacon_positions_per_scanner: Vec<Vec<Vec3di>> =
+            data.iter().map(|s| parse_chunks(s)).collect();
+        let fingerprints_per_scanner: Vec<Fingerprints> = beacon_positions_per_scanner
+            .iter()
+            .map(|coords| compute_fingerprints(coords))
+            .collect();
+        let (offsets, transformations, _) =
+            find_all_scanner_offsets(&fingerprints_per_scanner, DEFAULT_MIN_OVERLAP);
+        let poses = find_scanner_poses(data.len(), &offsets, &transformations).unwrap();
+        let map = build_scanner_map(&beacon_positions_per_scanner, &poses);
+
+        assert_eq!(map.beacons.len(), 79);
+        assert_eq!(map.scanners.len(), 5);
+        assert_eq!(map.scanners[2].0, Vec3di::new(1105, -1205, 1229));
+
+        // Each scanner's own view, rotated and moved into scanner 0 coordinates, is in the map
+        for (scanner, (position, rotation)) in map.scanners.iter().enumerate() {
+            for beacon in &beacon_positions_per_scanner[scanner] {
+                assert!(map.beacons.contains(&(position + &rotation.apply(beacon))));
+            }
+        }
+
+        let ply = map.export(PointCloudFormat::Ply);
+        assert!(ply.starts_with("ply\nformat ascii 1.0\nelement vertex 99\n"));
+        assert!(ply.contains("element edge 15\n"));
+        assert_eq!(ply.lines().count(), 13 + 99 + 15);
+
+        let obj = map.export(PointCloudFormat::Obj);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("v ")).count(), 99);
+        assert_eq!(obj.lines().filter(|l| l.starts_with("l ")).count(), 15);
+        assert!(obj.contains("o scanner_0\nv 0 0 0\nv 100 0 0\nv 0 100 0\nv 0 0 100\nl 80 81\n"));
+
+        let xyz = map.export(PointCloudFormat::Xyz);
+        assert!(xyz.starts_with("84\n"));
+        assert!(xyz.contains("S 1105 -1205 1229\n"));
+
+        let csv = map.export(PointCloudFormat::Csv);
+        assert_eq!(csv.lines().count(), 1 + 79 + 5);
+        assert!(csv.contains("scanner,0,0,0,0,1,0,0,0,1,0,0,0,1\n"));
+        assert!(csv.lines().all(|l| l.split(',').count() == 14));
+
+        let path = std::env::temp_dir().join("d19_test_map.ply");
+        file_io::write_string(&path, &ply).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), ply);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
@@ -385,24 +682,19 @@ mod tests {
         let data = file_io::read_lines_as_string_groups("inputs/d19").unwrap();
         let beacon_positions_per_scanner: Vec<Vec<Vec3di>> =
             data.iter().map(|s| parse_chunks(s)).collect();
-        let beacon_interdistances_per_scanner: Vec<DistanceMap> = beacon_positions_per_scanner
+        let fingerprints_per_scanner: Vec<Fingerprints> = beacon_positions_per_scanner
             .iter()
-            .map(|coords| compute_distances(coords))
+            .map(|coords| compute_fingerprints(coords))
             .collect();
 
-        let (offsets, transformations, paths) =
-            find_all_scanner_offsets(&beacon_interdistances_per_scanner);
-
-        let unique_beacons = find_unique_beacons(
-            &beacon_positions_per_scanner,
-            &offsets,
-            &transformations,
-            &paths,
-        );
+        let (offsets, transformations, _) =
+            find_all_scanner_offsets(&fingerprints_per_scanner, DEFAULT_MIN_OVERLAP);
+        let poses = find_scanner_poses(data.len(), &offsets, &transformations).unwrap();
 
+        let unique_beacons = find_unique_beacons(&beacon_positions_per_scanner, &poses);
         println!("Day 19 result #1: {}", unique_beacons.len());
 
-        let distance = compute_manhattan(&offsets, data.len());
+        let distance = compute_manhattan(&poses);
         println!("Day 19 result #2: {}", distance);
     }
 }
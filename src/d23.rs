@@ -0,0 +1,348 @@
+use crate::common::algorithms::search;
+use crate::common::error::CommonError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const HALLWAY_LEN: usize = 11;
+const NUM_ROOMS: usize = 4;
+const MAX_DEPTH: usize = 4;
+const ROOM_ENTRANCES: [usize; NUM_ROOMS] = [2, 4, 6, 8];
+const ENERGY: [u32; NUM_ROOMS] = [1, 10, 100, 1000];
+const CELL_BITS: usize = 3;
+const CELL_MASK: u128 = 0b111;
+
+// Rows inserted between the two folded rows for the second part
+const UNFOLDED_ROWS: [[u8; NUM_ROOMS]; 2] = [[3, 2, 1, 0], [3, 1, 0, 2]];
+
+/// Burrow with every cell packed into three bits of a single integer: hallway cells first, then
+/// each room from top to bottom. Zero is an empty cell, otherwise the amphipod type plus one.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone)]
+struct Burrow {
+    cells: u128,
+    depth: usize,
+}
+
+impl Burrow {
+    fn empty(depth: usize) -> Self {
+        assert!((1..=MAX_DEPTH).contains(&depth), "Unsupported room depth");
+        Burrow { cells: 0, depth }
+    }
+
+    fn room_cell(&self, room: usize, slot: usize) -> usize {
+        HALLWAY_LEN + room * self.depth + slot
+    }
+
+    fn get(&self, cell: usize) -> Option<u8> {
+        match (self.cells >> (cell * CELL_BITS)) & CELL_MASK {
+            0 => None,
+            v => Some(v as u8 - 1),
+        }
+    }
+
+    fn set(&mut self, cell: usize, value: Option<u8>) {
+        let shift = cell * CELL_BITS;
+        self.cells &= !(CELL_MASK << shift);
+        if let Some(kind) = value {
+            self.cells |= (kind as u128 + 1) << shift;
+        }
+    }
+
+    fn with_move(&self, from: usize, to: usize) -> Self {
+        let mut result = *self;
+        result.set(to, self.get(from));
+        result.set(from, None);
+        result
+    }
+
+    /// True if the room holds nothing but its own amphipods, so they never have to leave again.
+    fn is_room_settled(&self, room: usize) -> bool {
+        (0..self.depth).all(|slot| {
+            self.get(self.room_cell(room, slot))
+                .is_none_or(|kind| kind as usize == room)
+        })
+    }
+
+    fn is_solved(&self) -> bool {
+        (0..NUM_ROOMS).all(|room| {
+            (0..self.depth).all(|slot| self.get(self.room_cell(room, slot)) == Some(room as u8))
+        })
+    }
+
+    // Hallway cells strictly between `from` and `to`, plus `to` itself, have to be empty
+    fn is_hallway_clear(&self, from: usize, to: usize) -> bool {
+        let (lo, hi) = if from < to {
+            (from + 1, to)
+        } else {
+            (to, from - 1)
+        };
+        (lo..=hi).all(|cell| self.get(cell).is_none())
+    }
+
+    /// Inserts the two extra rows of the unfolded diagram between the first and second row.
+    fn unfold(&self) -> Self {
+        let mut result = Burrow::empty(self.depth + UNFOLDED_ROWS.len());
+        for cell in 0..HALLWAY_LEN {
+            result.set(cell, self.get(cell));
+        }
+        for room in 0..NUM_ROOMS {
+            let mut slots = vec![self.get(self.room_cell(room, 0))];
+            slots.extend(UNFOLDED_ROWS.iter().map(|row| Some(row[room])));
+            slots.extend((1..self.depth).map(|slot| self.get(self.room_cell(room, slot))));
+            for (slot, value) in slots.into_iter().enumerate() {
+                result.set(result.room_cell(room, slot), value);
+            }
+        }
+        result
+    }
+
+    /// All legal moves with their energy cost. Moving an amphipod into its room is never worse than
+    /// anything else, so when one is possible it is the only move returned.
+    fn moves(&self) -> Vec<(Burrow, u32)> {
+        for hallway in 0..HALLWAY_LEN {
+            if let Some(kind) = self.get(hallway) {
+                let room = kind as usize;
+                let entrance = ROOM_ENTRANCES[room];
+                if !self.is_room_settled(room) || !self.is_hallway_clear(hallway, entrance) {
+                    continue;
+                }
+                let slot = (0..self.depth)
+                    .rev()
+                    .find(|&slot| self.get(self.room_cell(room, slot)).is_none())
+                    .unwrap();
+                let steps = hallway.abs_diff(entrance) + slot + 1;
+                return vec![(
+                    self.with_move(hallway, self.room_cell(room, slot)),
+                    steps as u32 * ENERGY[room],
+                )];
+            }
+        }
+
+        let mut result = Vec::new();
+        for room in (0..NUM_ROOMS).filter(|&room| !self.is_room_settled(room)) {
+            let (slot, kind) = match (0..self.depth).find_map(|slot| {
+                self.get(self.room_cell(room, slot))
+                    .map(|kind| (slot, kind))
+            }) {
+                Some(top) => top,
+                None => continue,
+            };
+            let entrance = ROOM_ENTRANCES[room];
+            for hallway in (0..HALLWAY_LEN).filter(|h| !ROOM_ENTRANCES.contains(h)) {
+                if self.is_hallway_clear(entrance, hallway) {
+                    let steps = slot + 1 + entrance.abs_diff(hallway);
+                    result.push((
+                        self.with_move(self.room_cell(room, slot), hallway),
+                        steps as u32 * ENERGY[kind as usize],
+                    ));
+                }
+            }
+        }
+        result
+    }
+
+    /// Lower bound on the remaining energy: every misplaced amphipod has to walk at least to the top
+    /// of its own room, ignoring everyone else in the way.
+    fn min_remaining_energy(&self) -> u32 {
+        let mut result = 0;
+        for hallway in 0..HALLWAY_LEN {
+            if let Some(kind) = self.get(hallway) {
+                let steps = hallway.abs_diff(ROOM_ENTRANCES[kind as usize]) + 1;
+                result += steps as u32 * ENERGY[kind as usize];
+            }
+        }
+        for (room, &entrance) in ROOM_ENTRANCES.iter().enumerate() {
+            // Amphipods below the lowest stranger are already home for good
+            let must_leave_from = match (0..self.depth).rev().find(|&slot| {
+                self.get(self.room_cell(room, slot))
+                    .is_some_and(|kind| kind as usize != room)
+            }) {
+                Some(slot) => slot,
+                None => continue,
+            };
+            for slot in 0..=must_leave_from {
+                if let Some(kind) = self.get(self.room_cell(room, slot)) {
+                    let kind = kind as usize;
+                    // Someone leaving its own room has to step aside and come back
+                    let sideways = entrance.abs_diff(ROOM_ENTRANCES[kind]).max(2);
+                    let steps = slot + 1 + sideways + 1;
+                    result += steps as u32 * ENERGY[kind];
+                }
+            }
+        }
+        result
+    }
+}
+
+fn kind_to_char(kind: Option<u8>) -> char {
+    match kind {
+        None => '.',
+        Some(kind) => (b'A' + kind) as char,
+    }
+}
+
+fn char_to_kind(ch: char) -> Result<Option<u8>, CommonError> {
+    match ch {
+        '.' => Ok(None),
+        'A'..='D' => Ok(Some(ch as u8 - b'A')),
+        _ => Err(CommonError::Parse("Unknown character in burrow cell.")),
+    }
+}
+
+impl FromStr for Burrow {
+    type Err = CommonError;
+
+    /// Parses the puzzle diagram, with as many room rows as there are between the hallway and the
+    /// closing wall.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<Vec<char>> = s
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| line.chars().collect())
+            .collect();
+        if lines.len() < 4 {
+            return Err(CommonError::Parse("Burrow diagram is too short."));
+        }
+
+        let depth = lines.len() - 3;
+        if depth > MAX_DEPTH {
+            return Err(CommonError::Parse("Burrow rooms are too deep."));
+        }
+
+        let mut burrow = Burrow::empty(depth);
+        let hallway = &lines[1];
+        if hallway.len() < HALLWAY_LEN + 2 {
+            return Err(CommonError::Parse("Burrow hallway is too short."));
+        }
+        for cell in 0..HALLWAY_LEN {
+            burrow.set(cell, char_to_kind(hallway[cell + 1])?);
+        }
+
+        for slot in 0..depth {
+            let line = &lines[slot + 2];
+            for (room, &entrance) in ROOM_ENTRANCES.iter().enumerate() {
+                let &ch = line
+                    .get(entrance + 1)
+                    .ok_or(CommonError::Parse("Burrow room row is too short."))?;
+                burrow.set(burrow.room_cell(room, slot), char_to_kind(ch)?);
+            }
+        }
+
+        let mut counts = [0; NUM_ROOMS];
+        for cell in 0..HALLWAY_LEN + NUM_ROOMS * depth {
+            if let Some(kind) = burrow.get(cell) {
+                counts[kind as usize] += 1;
+            }
+        }
+        if counts.iter().any(|&count| count != depth) {
+            return Err(CommonError::Parse(
+                "Every amphipod type has to appear once per room row.",
+            ));
+        }
+
+        Ok(burrow)
+    }
+}
+
+impl Display for Burrow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", "#".repeat(HALLWAY_LEN + 2))?;
+        let hallway: String = (0..HALLWAY_LEN)
+            .map(|c| kind_to_char(self.get(c)))
+            .collect();
+        writeln!(f, "#{}#", hallway)?;
+        for slot in 0..self.depth {
+            let (left, right) = if slot == 0 { ("##", "##") } else { ("  ", "") };
+            let cells: Vec<String> = (0..NUM_ROOMS)
+                .map(|room| kind_to_char(self.get(self.room_cell(room, slot))).to_string())
+                .collect();
+            writeln!(f, "{}#{}#{}", left, cells.join("#"), right)?;
+        }
+        write!(f, "  {}", "#".repeat(HALLWAY_LEN - 2))
+    }
+}
+
+fn find_min_energy(burrow: &Burrow) -> Option<u32> {
+    search::astar(
+        *burrow,
+        Burrow::is_solved,
+        Burrow::moves,
+        Burrow::min_remaining_energy,
+    )
+    .map(|(energy, _)| energy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA: &str = r"#############
+#...........#
+###B#C#B#D###
+  #A#D#C#A#
+  #########";
+
+    const TEST_DATA_UNFOLDED: &str = r"#############
+#...........#
+###B#C#B#D###
+  #D#C#B#A#
+  #D#B#A#C#
+  #A#D#C#A#
+  #########";
+
+    #[test]
+    fn test_parse_display() {
+        let burrow: Burrow = TEST_DATA.parse().unwrap();
+        assert_eq!(burrow.depth, 2);
+        assert_eq!(burrow.to_string(), TEST_DATA);
+
+        let unfolded = burrow.unfold();
+        assert_eq!(unfolded.to_string(), TEST_DATA_UNFOLDED);
+        assert_eq!(unfolded, TEST_DATA_UNFOLDED.parse().unwrap());
+
+        let solved: Burrow =
+            "#############\n#...........#\n###A#B#C#D###\n  #A#B#C#D#\n  #########"
+                .parse()
+                .unwrap();
+        assert!(solved.is_solved());
+        assert!(solved.moves().is_empty());
+        assert_eq!(solved.min_remaining_energy(), 0);
+
+        assert!(TEST_DATA.replace('D', "A").parse::<Burrow>().is_err());
+        assert!(TEST_DATA.replace('D', "E").parse::<Burrow>().is_err());
+    }
+
+    #[test]
+    fn test_heuristic() {
+        // Heuristic is a lower bound along the optimal path
+        let burrow: Burrow = TEST_DATA.parse().unwrap();
+        let (energy, path) = search::astar(
+            burrow,
+            Burrow::is_solved,
+            Burrow::moves,
+            Burrow::min_remaining_energy,
+        )
+        .unwrap();
+        let (dijkstra_energy, _) =
+            search::dijkstra(burrow, Burrow::is_solved, Burrow::moves).unwrap();
+        assert_eq!(energy, dijkstra_energy);
+
+        let mut spent = 0;
+        for pair in path.windows(2) {
+            assert!(pair[0].min_remaining_energy() <= energy - spent);
+            spent += pair[0]
+                .moves()
+                .iter()
+                .find(|(next, _)| *next == pair[1])
+                .unwrap()
+                .1;
+        }
+        assert_eq!(spent, energy);
+    }
+
+    #[test]
+    fn test() {
+        let burrow: Burrow = TEST_DATA.parse().unwrap();
+        assert_eq!(find_min_energy(&burrow), Some(12521));
+        assert_eq!(find_min_energy(&burrow.unfold()), Some(44169));
+    }
+}
@@ -0,0 +1,504 @@
+use crate::common::error::CommonError;
+use std::str::FromStr;
+
+const NUM_REGISTERS: usize = 4;
+const BLOCK_LEN: usize = 18;
+const Z_BASE: i64 = 26;
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Register {
+    W,
+    X,
+    Y,
+    Z,
+}
+
+impl Register {
+    fn index(&self) -> usize {
+        match self {
+            Register::W => 0,
+            Register::X => 1,
+            Register::Y => 2,
+            Register::Z => 3,
+        }
+    }
+}
+
+impl FromStr for Register {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "w" => Ok(Register::W),
+            "x" => Ok(Register::X),
+            "y" => Ok(Register::Y),
+            "z" => Ok(Register::Z),
+            _ => Err(CommonError::Parse("Unknown register.")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Operand {
+    Register(Register),
+    Literal(i64),
+}
+
+impl FromStr for Operand {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<Register>() {
+            Ok(register) => Ok(Operand::Register(register)),
+            Err(_) => Ok(Operand::Literal(s.parse().map_err(|_| {
+                CommonError::Parse("Operand is neither a register nor a number.")
+            })?)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+enum Instruction {
+    Inp(Register),
+    Add(Register, Operand),
+    Mul(Register, Operand),
+    Div(Register, Operand),
+    Mod(Register, Operand),
+    Eql(Register, Operand),
+}
+
+impl FromStr for Instruction {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split_whitespace().collect();
+        match parts.as_slice() {
+            ["inp", a] => Ok(Instruction::Inp(a.parse()?)),
+            [op, a, b] => {
+                let (a, b) = (a.parse()?, b.parse()?);
+                match *op {
+                    "add" => Ok(Instruction::Add(a, b)),
+                    "mul" => Ok(Instruction::Mul(a, b)),
+                    "div" => Ok(Instruction::Div(a, b)),
+                    "mod" => Ok(Instruction::Mod(a, b)),
+                    "eql" => Ok(Instruction::Eql(a, b)),
+                    _ => Err(CommonError::Parse("Unknown instruction.")),
+                }
+            }
+            _ => Err(CommonError::Parse("Malformed instruction.")),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+struct Alu {
+    registers: [i64; NUM_REGISTERS],
+}
+
+impl Alu {
+    fn get(&self, register: Register) -> i64 {
+        self.registers[register.index()]
+    }
+
+    fn value(&self, operand: &Operand) -> i64 {
+        match operand {
+            Operand::Register(register) => self.get(*register),
+            Operand::Literal(value) => *value,
+        }
+    }
+
+    /// Runs the program from a zeroed state, taking `inp` values from `input` in order.
+    fn run(program: &[Instruction], input: &[i64]) -> Result<Alu, CommonError> {
+        let mut alu = Alu::default();
+        let mut input = input.iter();
+
+        for (pc, instruction) in program.iter().enumerate() {
+            let (target, result) = match instruction {
+                Instruction::Inp(a) => (
+                    a,
+                    *input.next().ok_or_else(|| {
+                        CommonError::Evaluation(format!("Ran out of input at instruction {}", pc))
+                    })?,
+                ),
+                Instruction::Add(a, b) => {
+                    let (lhs, rhs) = (alu.get(*a), alu.value(b));
+                    let sum = lhs.checked_add(rhs).ok_or_else(|| {
+                        CommonError::Overflow(format!(
+                            "{} + {} overflows at instruction {}",
+                            lhs, rhs, pc
+                        ))
+                    })?;
+                    (a, sum)
+                }
+                Instruction::Mul(a, b) => {
+                    let (lhs, rhs) = (alu.get(*a), alu.value(b));
+                    let product = lhs.checked_mul(rhs).ok_or_else(|| {
+                        CommonError::Overflow(format!(
+                            "{} * {} overflows at instruction {}",
+                            lhs, rhs, pc
+                        ))
+                    })?;
+                    (a, product)
+                }
+                Instruction::Div(a, b) => {
+                    let divisor = alu.value(b);
+                    if divisor == 0 {
+                        return Err(CommonError::Evaluation(format!(
+                            "Division by zero at instruction {}",
+                            pc
+                        )));
+                    }
+                    (a, alu.get(*a) / divisor)
+                }
+                Instruction::Mod(a, b) => {
+                    let (lhs, rhs) = (alu.get(*a), alu.value(b));
+                    if lhs < 0 || rhs <= 0 {
+                        return Err(CommonError::Evaluation(format!(
+                            "Invalid modulo {} % {} at instruction {}",
+                            lhs, rhs, pc
+                        )));
+                    }
+                    (a, lhs % rhs)
+                }
+                Instruction::Eql(a, b) => (a, (alu.get(*a) == alu.value(b)) as i64),
+            };
+            alu.registers[target.index()] = result;
+        }
+
+        Ok(alu)
+    }
+}
+
+fn parse_program<S: AsRef<str>>(lines: &[S]) -> Result<Vec<Instruction>, CommonError> {
+    lines
+        .iter()
+        .map(|line| line.as_ref().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| line.parse())
+        .collect()
+}
+
+/// The three literals that differ between the per-digit blocks of MONAD. Each block treats `z` as a
+/// base 26 stack: it pops when `divisor` is 26, then pushes `w + y_offset` unless the popped value
+/// plus `x_offset` equals the input digit.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+struct BlockParams {
+    divisor: i64,
+    x_offset: i64,
+    y_offset: i64,
+}
+
+impl BlockParams {
+    fn to_instructions(self) -> Vec<Instruction> {
+        use Instruction::*;
+        use Register::*;
+
+        vec![
+            Inp(W),
+            Mul(X, Operand::Literal(0)),
+            Add(X, Operand::Register(Z)),
+            Mod(X, Operand::Literal(Z_BASE)),
+            Div(Z, Operand::Literal(self.divisor)),
+            Add(X, Operand::Literal(self.x_offset)),
+            Eql(X, Operand::Register(W)),
+            Eql(X, Operand::Literal(0)),
+            Mul(Y, Operand::Literal(0)),
+            Add(Y, Operand::Literal(Z_BASE - 1)),
+            Mul(Y, Operand::Register(X)),
+            Add(Y, Operand::Literal(1)),
+            Mul(Z, Operand::Register(Y)),
+            Mul(Y, Operand::Literal(0)),
+            Add(Y, Operand::Register(W)),
+            Add(Y, Operand::Literal(self.y_offset)),
+            Mul(Y, Operand::Register(X)),
+            Add(Z, Operand::Register(Y)),
+        ]
+    }
+}
+
+/// Splits the program into per-digit blocks and extracts their parameters, failing if any block
+/// deviates from the expected shape.
+fn analyze_blocks(program: &[Instruction]) -> Result<Vec<BlockParams>, CommonError> {
+    if !program.len().is_multiple_of(BLOCK_LEN) {
+        return Err(CommonError::Evaluation(format!(
+            "Program length {} is not a multiple of the block length",
+            program.len()
+        )));
+    }
+
+    program
+        .chunks(BLOCK_LEN)
+        .enumerate()
+        .map(|(idx, block)| {
+            let literal = |pos: usize| match block[pos] {
+                Instruction::Div(_, Operand::Literal(v))
+                | Instruction::Add(_, Operand::Literal(v)) => Some(v),
+                _ => None,
+            };
+            let params = match (literal(4), literal(5), literal(15)) {
+                (Some(divisor), Some(x_offset), Some(y_offset)) => BlockParams {
+                    divisor,
+                    x_offset,
+                    y_offset,
+                },
+                _ => {
+                    return Err(CommonError::Evaluation(format!(
+                        "Block {} has no parameters where expected",
+                        idx
+                    )))
+                }
+            };
+
+            if params.to_instructions() != block {
+                return Err(CommonError::Evaluation(format!(
+                    "Block {} does not match the MONAD block shape",
+                    idx
+                )));
+            }
+            Ok(params)
+        })
+        .collect()
+}
+
+// Every pushing block is matched with the popping block that removes its value again. For z to end
+// at zero, each popping block's digit has to equal the pushing digit plus the pushed y_offset plus
+// its own x_offset, which pins the pair down independently of all others.
+fn solve_model_number(blocks: &[BlockParams], largest: bool) -> Result<Vec<i64>, CommonError> {
+    let mut digits = vec![0; blocks.len()];
+    let mut stack: Vec<(usize, i64)> = Vec::new();
+
+    for (idx, block) in blocks.iter().enumerate() {
+        match block.divisor {
+            1 => {
+                // A pushing block whose comparison could succeed would break the stack pairing
+                if block.x_offset < 10 {
+                    return Err(CommonError::Evaluation(format!(
+                        "Pushing block {} may skip its push",
+                        idx
+                    )));
+                }
+                stack.push((idx, block.y_offset));
+            }
+            Z_BASE => {
+                let (pushed_idx, y_offset) = stack.pop().ok_or_else(|| {
+                    CommonError::Evaluation(format!("Block {} pops an empty stack", idx))
+                })?;
+                let diff = y_offset + block.x_offset;
+                let pushed_digit = if largest {
+                    9.min(9 - diff)
+                } else {
+                    1.max(1 - diff)
+                };
+                let digit = pushed_digit + diff;
+                if !(1..=9).contains(&pushed_digit) || !(1..=9).contains(&digit) {
+                    return Err(CommonError::Evaluation(format!(
+                        "Blocks {} and {} cannot be satisfied",
+                        pushed_idx, idx
+                    )));
+                }
+                digits[pushed_idx] = pushed_digit;
+                digits[idx] = digit;
+            }
+            other => {
+                return Err(CommonError::Evaluation(format!(
+                    "Block {} divides z by {}",
+                    idx, other
+                )))
+            }
+        }
+    }
+
+    if !stack.is_empty() {
+        return Err(CommonError::Evaluation(format!(
+            "{} pushes are never popped",
+            stack.len()
+        )));
+    }
+    Ok(digits)
+}
+
+fn digits_to_number(digits: &[i64]) -> u64 {
+    digits.iter().fold(0, |acc, &d| acc * 10 + d as u64)
+}
+
+/// Finds the largest or smallest accepted model number, double checked by running the program.
+fn find_model_number(program: &[Instruction], largest: bool) -> Result<u64, CommonError> {
+    let blocks = analyze_blocks(program)?;
+    let digits = solve_model_number(&blocks, largest)?;
+
+    let alu = Alu::run(program, &digits)?;
+    if alu.get(Register::Z) != 0 {
+        return Err(CommonError::Evaluation(format!(
+            "Program rejects the solved model number {}",
+            digits_to_number(&digits)
+        )));
+    }
+    Ok(digits_to_number(&digits))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA_BINARY: &str = r"inp w
+add z w
+mod z 2
+div w 2
+add y w
+mod y 2
+div w 2
+add x w
+mod x 2
+div w 2
+mod w 2";
+
+    // (divisor, x_offset, y_offset) for a made up fourteen digit MONAD
+    const TEST_BLOCKS: [(i64, i64, i64); 14] = [
+        (1, 12, 4),
+        (1, 11, 11),
+        (1, 13, 5),
+        (1, 11, 11),
+        (1, 14, 14),
+        (26, -10, 7),
+        (1, 11, 11),
+        (26, -9, 4),
+        (26, -3, 6),
+        (1, 13, 5),
+        (26, -5, 9),
+        (26, -10, 12),
+        (26, -4, 14),
+        (26, -5, 14),
+    ];
+
+    fn build_program(blocks: &[(i64, i64, i64)]) -> Vec<Instruction> {
+        blocks
+            .iter()
+            .flat_map(|&(divisor, x_offset, y_offset)| {
+                BlockParams {
+                    divisor,
+                    x_offset,
+                    y_offset,
+                }
+                .to_instructions()
+            })
+            .collect()
+    }
+
+    fn accepts(program: &[Instruction], digits: &[i64]) -> bool {
+        Alu::run(program, digits).unwrap().get(Register::Z) == 0
+    }
+
+    #[test]
+    fn test_interpreter() {
+        let negate = parse_program(&["inp x", "mul x -1"]).unwrap();
+        assert_eq!(Alu::run(&negate, &[7]).unwrap().get(Register::X), -7);
+
+        let triple = parse_program(&["inp z", "inp x", "mul z 3", "eql z x"]).unwrap();
+        assert_eq!(Alu::run(&triple, &[3, 9]).unwrap().get(Register::Z), 1);
+        assert_eq!(Alu::run(&triple, &[3, 8]).unwrap().get(Register::Z), 0);
+
+        let lines: Vec<&str> = TEST_DATA_BINARY.lines().collect();
+        let binary = parse_program(&lines).unwrap();
+        assert_eq!(
+            Alu::run(&binary, &[11]).unwrap().registers,
+            [1, 0, 1, 1] // w x y z hold the bits from most to least significant
+        );
+
+        assert!(Alu::run(&negate, &[]).is_err());
+        assert!(Alu::run(&parse_program(&["div x 0"]).unwrap(), &[]).is_err());
+        assert!(Alu::run(&parse_program(&["add x -1", "mod x 2"]).unwrap(), &[]).is_err());
+
+        // Registers are 64 bits wide, so repeated scaling has to report the overflow
+        let scale = parse_program(&["inp z", "mul z 26", "mul z 26", "add z 1"]).unwrap();
+        assert_eq!(Alu::run(&scale, &[1000]).unwrap().get(Register::Z), 676001);
+        assert!(matches!(
+            Alu::run(&scale, &[i64::MAX / 100]),
+            Err(CommonError::Overflow(_))
+        ));
+        assert!(matches!(
+            Alu::run(&parse_program(&["inp x", "add x 1"]).unwrap(), &[i64::MAX]),
+            Err(CommonError::Overflow(_))
+        ));
+
+        assert!("inp q".parse::<Instruction>().is_err());
+        assert!("sub x 1".parse::<Instruction>().is_err());
+        assert!("add x".parse::<Instruction>().is_err());
+        assert_eq!(
+            "eql x -12".parse::<Instruction>().unwrap(),
+            Instruction::Eql(Register::X, Operand::Literal(-12))
+        );
+    }
+
+    #[test]
+    fn test_analyze_blocks() {
+        let program = build_program(&TEST_BLOCKS);
+        let blocks = analyze_blocks(&program).unwrap();
+        assert_eq!(blocks.len(), 14);
+        assert_eq!(
+            blocks[5],
+            BlockParams {
+                divisor: 26,
+                x_offset: -10,
+                y_offset: 7
+            }
+        );
+
+        let mut tampered = program.clone();
+        tampered[7] = Instruction::Eql(Register::X, Operand::Literal(1));
+        assert!(analyze_blocks(&tampered).is_err());
+        assert!(analyze_blocks(&program[1..]).is_err());
+
+        let unbalanced = build_program(&TEST_BLOCKS[..13]);
+        assert!(find_model_number(&unbalanced, true).is_err());
+    }
+
+    #[test]
+    fn test_small_against_brute_force() {
+        let blocks = [(1, 10, 3), (1, 15, 0), (26, 1, 5), (26, -7, 0)];
+        let program = build_program(&blocks);
+
+        let valid: Vec<u64> = (0..9u32.pow(4))
+            .map(|n| {
+                (0..4)
+                    .rev()
+                    .map(|i| (n / 9u32.pow(i) % 9) as i64 + 1)
+                    .collect::<Vec<i64>>()
+            })
+            .filter(|digits| accepts(&program, digits))
+            .map(|digits| digits_to_number(&digits))
+            .collect();
+
+        assert_eq!(
+            find_model_number(&program, true).unwrap(),
+            *valid.iter().max().unwrap()
+        );
+        assert_eq!(
+            find_model_number(&program, false).unwrap(),
+            *valid.iter().min().unwrap()
+        );
+    }
+
+    #[test]
+    fn test() {
+        let program = build_program(&TEST_BLOCKS);
+        let largest = find_model_number(&program, true).unwrap();
+        let smallest = find_model_number(&program, false).unwrap();
+
+        // Bumping any single digit of the largest number must either break it or leave 1..=9
+        let digits: Vec<i64> = largest
+            .to_string()
+            .chars()
+            .map(|c| c.to_digit(10).unwrap() as i64)
+            .collect();
+        for idx in 0..digits.len() {
+            for bumped in digits[idx] + 1..=9 {
+                let mut candidate = digits.clone();
+                candidate[idx] = bumped;
+                assert!(!accepts(&program, &candidate));
+            }
+        }
+
+        assert_eq!(largest, 92915979999498);
+        assert_eq!(smallest, 21611513911181);
+    }
+}
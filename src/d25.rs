@@ -0,0 +1,189 @@
+use crate::common::collections::dense_grid::{ArrayGrid, DenseGrid};
+use crate::common::error::CommonError;
+use std::collections::HashMap;
+
+const EAST: char = '>';
+const SOUTH: char = 'v';
+const EMPTY: char = '.';
+
+// Moves every cucumber of one herd whose target cell is free in `grid` as it was before the herd
+// moved, writing into a copy so nobody sees a neighbour's move from the same step.
+fn move_herd(grid: &ArrayGrid<char>, herd: char, dx: usize, dy: usize) -> (ArrayGrid<char>, usize) {
+    let mut next = grid.clone();
+    let mut num_moved = 0;
+
+    for y in 0..grid.y_size {
+        for x in 0..grid.x_size {
+            if *grid.get_pos(x, y) != herd {
+                continue;
+            }
+            let x_new = (x + dx) % grid.x_size;
+            let y_new = (y + dy) % grid.y_size;
+            if *grid.get_pos(x_new, y_new) == EMPTY {
+                next.set_pos(x, y, EMPTY);
+                next.set_pos(x_new, y_new, herd);
+                num_moved += 1;
+            }
+        }
+    }
+
+    (next, num_moved)
+}
+
+/// One step, the east-facing herd first and then the south-facing one. Returns the new grid and
+/// the number of cucumbers that moved.
+fn step(grid: &ArrayGrid<char>) -> (ArrayGrid<char>, usize) {
+    let (grid, moved_east) = move_herd(grid, EAST, 1, 0);
+    let (grid, moved_south) = move_herd(&grid, SOUTH, 0, 1);
+    (grid, moved_east + moved_south)
+}
+
+fn simulate(grid: &ArrayGrid<char>, num_steps: usize) -> ArrayGrid<char> {
+    (0..num_steps).fold(grid.clone(), |grid, _| step(&grid).0)
+}
+
+/// Number of the first step on which no sea cucumber moves, taking at most `budget` steps. Herds
+/// can also end up moving in circles forever, which is an error.
+fn find_first_still_step(grid: &ArrayGrid<char>, budget: usize) -> Result<usize, CommonError> {
+    let mut cur = grid.clone();
+    let mut seen = HashMap::from([(cur.underlying.clone(), 0)]);
+
+    for step_num in 1..=budget {
+        let (next, num_moved) = step(&cur);
+        if num_moved == 0 {
+            return Ok(step_num);
+        }
+        if let Some(start) = seen.insert(next.underlying.clone(), step_num) {
+            return Err(CommonError::Evaluation(format!(
+                "Sea cucumbers keep moving in a cycle of {} steps from step {}",
+                step_num - start,
+                start
+            )));
+        }
+        cur = next;
+    }
+    Err(CommonError::StepBudget(budget))
+}
+
+/// Renders the grid after each of the given step counts, laid out like the puzzle's walkthrough.
+fn render_steps(grid: &ArrayGrid<char>, steps: &[usize]) -> String {
+    let mut sections = Vec::with_capacity(steps.len());
+    let mut cur = grid.clone();
+    let mut cur_step = 0;
+
+    for &target in steps {
+        assert!(target >= cur_step, "Steps have to be in increasing order");
+        cur = simulate(&cur, target - cur_step);
+        cur_step = target;
+
+        let header = match target {
+            0 => "Initial state:".to_string(),
+            1 => "After 1 step:".to_string(),
+            n => format!("After {} steps:", n),
+        };
+        sections.push(format!("{}\n{}", header, cur));
+    }
+
+    sections.join("\n\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DATA: &str = r"v...>>.vv>
+.vv>>.vv..
+>>.>v>...v
+>>v>>.>.v.
+v>v.vv.v..
+>.>>..v...
+.vv..>.>v.
+v.v..>>v.v
+....v..v.>";
+
+    const TEST_DATA_STEP_1: &str = r"....>.>v.>
+v.v>.>v.v.
+>v>>..>v..
+>>v>v>.>.v
+.>v.v...v.
+v>>.>vvv..
+..v...>>..
+vv...>>vv.
+>.v.v..v.v";
+
+    const TEST_DATA_WRAP: &str = r"...>...
+.......
+......>
+v.....>
+......>
+.......
+..vvv..";
+
+    const TEST_DATA_WRAP_STEP_1: &str = r"..vv>..
+.......
+>......
+v.....>
+>......
+.......
+....v..";
+
+    #[test]
+    fn test_step() {
+        let grid: ArrayGrid<char> = "...>>>>>...\n".parse().unwrap();
+        assert_eq!(simulate(&grid, 1).to_string(), "...>>>>.>..");
+        assert_eq!(simulate(&grid, 2).to_string(), "...>>>.>.>.");
+
+        // The south herd sees where the east herd moved during the same step
+        let grid: ArrayGrid<char> = "..........\n.>v....v..\n.......>..\n.........."
+            .parse()
+            .unwrap();
+        assert_eq!(
+            simulate(&grid, 1).to_string(),
+            "..........\n.>........\n..v....v>.\n.........."
+        );
+
+        let grid: ArrayGrid<char> = TEST_DATA_WRAP.parse().unwrap();
+        assert_eq!(simulate(&grid, 1).to_string(), TEST_DATA_WRAP_STEP_1);
+        assert_eq!(
+            render_steps(&grid, &[0, 1]),
+            format!(
+                "Initial state:\n{}\n\nAfter 1 step:\n{}",
+                TEST_DATA_WRAP, TEST_DATA_WRAP_STEP_1
+            )
+        );
+    }
+
+    #[test]
+    fn test_never_still() {
+        // A lone cucumber on a ring never stops
+        let grid: ArrayGrid<char> = ">..\n...".parse().unwrap();
+        match find_first_still_step(&grid, 1000) {
+            Err(CommonError::Evaluation(msg)) => assert!(msg.contains("cycle of 3 steps")),
+            other => panic!("Expected a cycle, got {:?}", other),
+        }
+
+        let grid: ArrayGrid<char> = TEST_DATA.parse().unwrap();
+        assert!(matches!(
+            find_first_still_step(&grid, 50),
+            Err(CommonError::StepBudget(50))
+        ));
+    }
+
+    #[test]
+    fn test() {
+        let grid: ArrayGrid<char> = TEST_DATA.parse().unwrap();
+        assert_eq!(simulate(&grid, 1).to_string(), TEST_DATA_STEP_1);
+        assert_eq!(find_first_still_step(&grid, 1000).unwrap(), 58);
+        assert_eq!(find_first_still_step(&grid, 58).unwrap(), 58);
+        assert!(matches!(
+            find_first_still_step(&grid, 57),
+            Err(CommonError::StepBudget(57))
+        ));
+
+        // Nothing moves any more on the 58th step
+        let rendered = render_steps(&grid, &[57, 58]);
+        let (before, after) = rendered.split_once("\n\n").unwrap();
+        assert!(before.starts_with("After 57 steps:\n"));
+        assert_eq!(&before[16..], &after[16..]);
+    }
+}
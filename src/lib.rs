@@ -20,3 +20,7 @@ mod d18;
 mod d19;
 mod d20;
 mod d21;
+mod d22;
+mod d23;
+mod d24;
+mod d25;